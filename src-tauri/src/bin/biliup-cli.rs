@@ -0,0 +1,7 @@
+// 控制台程序，Windows 上输出和退出码直接回到启动它的终端
+use std::process::ExitCode;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    biliup_app_lib::cli::run(std::env::args().skip(1).collect()).await
+}
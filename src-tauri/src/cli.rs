@@ -0,0 +1,422 @@
+//! 无界面的命令行入口，与桌面端共用 config.json 中的账号和模板
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{IsTerminal, Write},
+    path::PathBuf,
+    process::ExitCode,
+    sync::Arc,
+    time::Duration,
+};
+
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::{
+    connect,
+    models::{ConfigRoot, Draft, QueueStats, SourceFingerprint, VideoInfo},
+    services::{
+        media_probe, submit_pipeline, translation::TranslationService,
+        upload_service::UploadService,
+    },
+    setup_logs,
+    utils::get_config_json_path,
+};
+
+const USAGE: &str = "用法: biliup-cli upload --user <uid> --template <模板名> <文件>...
+
+上传文件并使用桌面端保存的模板投稿，账号和模板读取自 config.json
+
+选项:
+  -u, --user <uid>         已登录账号的 uid
+  -t, --template <模板名>  投稿使用的模板
+  -h, --help               显示帮助
+
+退出码:
+  0    投稿成功
+  1    上传失败
+  2    投稿失败
+  3    参数错误
+  4    配置或登录失败
+  130  被中断";
+
+/// 非终端输出时打印进度的间隔
+const PLAIN_PROGRESS_INTERVAL: u64 = 10;
+
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Config(String),
+    Upload(String),
+    Submit(String),
+    Interrupted,
+}
+
+impl CliError {
+    fn code(&self) -> u8 {
+        match self {
+            CliError::Upload(_) => 1,
+            CliError::Submit(_) => 2,
+            CliError::Usage(_) => 3,
+            CliError::Config(_) => 4,
+            CliError::Interrupted => 130,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(msg) => write!(f, "参数错误: {msg}"),
+            CliError::Config(msg) => write!(f, "配置错误: {msg}"),
+            CliError::Upload(msg) => write!(f, "上传失败: {msg}"),
+            CliError::Submit(msg) => write!(f, "投稿失败: {msg}"),
+            CliError::Interrupted => write!(f, "已中断"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct UploadArgs {
+    uid: u64,
+    template: String,
+    files: Vec<PathBuf>,
+}
+
+/// 命令行入口，args 不含程序名
+pub async fn run(args: Vec<String>) -> ExitCode {
+    if args.is_empty() || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    let result = match parse_args(&args) {
+        Ok(args) => tokio::select! {
+            result = upload(args) => result,
+            _ = tokio::signal::ctrl_c() => Err(CliError::Interrupted),
+        },
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(data) => {
+            let aid = data["aid"].as_u64().unwrap_or(0);
+            let bvid = data["bvid"].as_str().unwrap_or("");
+            println!("投稿成功: av{aid} {bvid}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            if matches!(e, CliError::Usage(_)) {
+                eprintln!("\n{USAGE}");
+            }
+            error!("命令行上传结束: {}", e);
+            ExitCode::from(e.code())
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<UploadArgs, CliError> {
+    let (command, rest) = args
+        .split_first()
+        .ok_or_else(|| CliError::Usage("缺少子命令".to_string()))?;
+    if command != "upload" {
+        return Err(CliError::Usage(format!("未知的子命令: {command}")));
+    }
+
+    let mut uid = None;
+    let mut template = None;
+    let mut files = Vec::new();
+    let mut rest = rest.iter();
+    let mut options_done = false;
+    while let Some(arg) = rest.next() {
+        if options_done || !arg.starts_with('-') {
+            files.push(PathBuf::from(arg));
+            continue;
+        }
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| rest.next().cloned())
+                .ok_or_else(|| CliError::Usage(format!("{name} 缺少参数值")))
+        };
+        match name {
+            "--" => options_done = true,
+            "-u" | "--user" => {
+                let value = value()?;
+                uid = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| CliError::Usage(format!("无效的 uid: {value}")))?,
+                );
+            }
+            "-t" | "--template" => template = Some(value()?),
+            _ => return Err(CliError::Usage(format!("未知的选项: {name}"))),
+        }
+    }
+
+    let uid = uid.ok_or_else(|| CliError::Usage("缺少 --user".to_string()))?;
+    let template = template.ok_or_else(|| CliError::Usage("缺少 --template".to_string()))?;
+    if files.is_empty() {
+        return Err(CliError::Usage("至少需要一个文件".to_string()));
+    }
+    if let Some(missing) = files.iter().find(|file| !file.is_file()) {
+        return Err(CliError::Usage(format!(
+            "文件不存在: {}",
+            missing.display()
+        )));
+    }
+    Ok(UploadArgs {
+        uid,
+        template,
+        files,
+    })
+}
+
+async fn upload(args: UploadArgs) -> Result<Value, CliError> {
+    let config_path =
+        get_config_json_path().map_err(|e| CliError::Config(format!("获取配置路径失败: {e}")))?;
+    let config = ConfigRoot::from_file(&config_path)
+        .map_err(|e| CliError::Config(format!("读取 {} 失败: {e}", config_path.display())))?;
+    // 日志只写入文件，终端留给进度输出
+    if let Err(e) = setup_logs(&config.log_level, false) {
+        eprintln!("日志初始化失败: {e}");
+    }
+
+    let user_config = config
+        .config
+        .get(&args.uid)
+        .ok_or_else(|| CliError::Config(format!("账号未登录: {}", args.uid)))?;
+    if !user_config.templates.contains_key(&args.template) {
+        return Err(CliError::Config(format!("模板不存在: {}", args.template)));
+    }
+    let (uid, client) = connect(user_config)
+        .await
+        .map_err(|e| CliError::Config(format!("登录失败: {e}")))?;
+    if uid != args.uid {
+        return Err(CliError::Config(format!(
+            "账号 {} 保存的登录信息属于 {}",
+            args.uid, uid
+        )));
+    }
+    let user = client.user.clone();
+    eprintln!("账号: {} ({})", user.username, uid);
+
+    let config_snapshot = config.clone();
+    let max_curr = config.max_curr;
+    let config = Arc::new(Mutex::new(config));
+    let clients = Arc::new(Mutex::new(HashMap::from([(uid, client)])));
+    let mut upload_service = UploadService::new(max_curr, Arc::clone(&config));
+    upload_service.disable_persistence().await;
+    upload_service
+        .bandwidth()
+        .apply_config(&*config.lock().await);
+
+    let translation = TranslationService::new();
+    let created_at = chrono::Utc::now().timestamp_millis();
+    let mut task_ids = Vec::with_capacity(args.files.len());
+    for (index, file) in args.files.iter().enumerate() {
+        // 账本和队列中记录绝对路径
+        let file = file.canonicalize().unwrap_or_else(|_| file.clone());
+        let mut video = VideoInfo::from_path(format!("cli-{created_at}-{index}"), &file);
+        video.probe = media_probe::check(&video.path, &config_snapshot.probe)
+            .await
+            .map_err(CliError::Upload)?;
+        translation
+            .auto_translate(&config_snapshot, &mut video)
+            .await;
+        let source = SourceFingerprint::read_async(&file)
+            .await
+            .map_err(|e| CliError::Upload(format!("读取文件失败: {} - {e}", file.display())))?;
+        upload_service
+            .create_task(
+                &user,
+                &args.template,
+                &video,
+                Some(source),
+                Arc::clone(&config),
+                Arc::clone(&clients),
+            )
+            .await
+            .map_err(|e| CliError::Upload(e.to_string()))?;
+        upload_service
+            .start_upload(&video.id)
+            .await
+            .map_err(|e| CliError::Upload(e.to_string()))?;
+        task_ids.push(video.id);
+    }
+    info!("命令行上传: {} 个文件 -> {}", task_ids.len(), args.template);
+
+    wait_uploads(&mut upload_service).await?;
+
+    eprintln!("上传完成，开始投稿: {}", args.template);
+    let tasks = upload_service
+        .tasks(&task_ids)
+        .await
+        .map_err(|e| CliError::Submit(e.to_string()))?;
+    let draft = Draft::new(uid, &args.template, task_ids);
+    submit_pipeline::submit_draft(&draft, &tasks)
+        .await
+        .map_err(|e| CliError::Submit(e.to_string()))
+}
+
+/// 等待队列中的任务全部完成，有任务最终失败时返回错误
+async fn wait_uploads(upload_service: &mut UploadService) -> Result<(), CliError> {
+    let terminal = std::io::stderr().is_terminal();
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut ticks: u64 = 0;
+    loop {
+        interval.tick().await;
+        ticks += 1;
+
+        let tasks = upload_service
+            .get_upload_queue()
+            .await
+            .map_err(|e| CliError::Upload(e.to_string()))?;
+        if let Some(task) = tasks.iter().find(|task| task.is_failed()) {
+            if terminal {
+                eprintln!();
+            }
+            return Err(CliError::Upload(format!(
+                "{} - {}",
+                task.video.title,
+                task.error_message.clone().unwrap_or_default()
+            )));
+        }
+
+        let stats = upload_service.get_queue_stats().await;
+        let line = progress_line(&stats);
+        if stats.completed == stats.total {
+            if terminal {
+                eprintln!("\r{line}\x1b[K");
+            } else {
+                eprintln!("{line}");
+            }
+            return Ok(());
+        }
+        if terminal {
+            eprint!("\r{line}\x1b[K");
+            let _ = std::io::stderr().flush();
+        } else if ticks.is_multiple_of(PLAIN_PROGRESS_INTERVAL) {
+            eprintln!("{line}");
+        }
+    }
+}
+
+fn progress_line(stats: &QueueStats) -> String {
+    let percent = if stats.total_bytes > 0 {
+        stats.uploaded_bytes as f64 / stats.total_bytes as f64 * 100.0
+    } else {
+        0.0
+    };
+    let eta = stats
+        .eta_secs
+        .map(|secs| format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60))
+        .unwrap_or_else(|| "--:--:--".to_string());
+    format!(
+        "[{}/{}] {:5.1}%  {} / {}  {}/s  剩余 {}",
+        stats.completed,
+        stats.total,
+        percent,
+        format_bytes(stats.uploaded_bytes as f64),
+        format_bytes(stats.total_bytes as f64),
+        format_bytes(stats.speed_bytes_per_sec),
+        eta
+    )
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 参数检查要求文件存在，用清单文件代替视频
+    fn file() -> String {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml").to_string()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_upload_args() {
+        let file = file();
+        let parsed = parse_args(&args(&["upload", "-u", "42", "--template=默认", &file])).unwrap();
+        assert_eq!(parsed.uid, 42);
+        assert_eq!(parsed.template, "默认");
+        assert_eq!(parsed.files, [PathBuf::from(&file)]);
+
+        // -- 之后的参数都是文件
+        let parsed = parse_args(&args(&[
+            "upload",
+            "--user=42",
+            "-t",
+            "默认",
+            "--",
+            &file,
+            &file,
+        ]))
+        .unwrap();
+        assert_eq!(parsed.files.len(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_args() {
+        let file = file();
+        for invalid in [
+            args(&["submit", "-u", "42", "-t", "默认", &file]),
+            args(&["upload", "-u", "abc", "-t", "默认", &file]),
+            args(&["upload", "-t", "默认", &file]),
+            args(&["upload", "-u", "42", &file]),
+            args(&["upload", "-u", "42", "-t", "默认"]),
+            args(&["upload", "-u", "42", "-t"]),
+            args(&["upload", "-u", "42", "-t", "默认", "--force", &file]),
+            args(&["upload", "-u", "42", "-t", "默认", "/nonexistent/video.mp4"]),
+        ] {
+            let err = parse_args(&invalid).unwrap_err();
+            assert!(matches!(err, CliError::Usage(_)), "{invalid:?}");
+            assert_eq!(err.code(), 3);
+        }
+    }
+
+    #[test]
+    fn formats_progress() {
+        let mut stats = QueueStats {
+            total: 2,
+            completed: 1,
+            total_bytes: 4 * 1024 * 1024,
+            uploaded_bytes: 1024 * 1024,
+            speed_bytes_per_sec: 512.0 * 1024.0,
+            eta_secs: Some(3725),
+            ..Default::default()
+        };
+        assert_eq!(
+            progress_line(&stats),
+            "[1/2]  25.0%  1.0 MiB / 4.0 MiB  512.0 KiB/s  剩余 01:02:05"
+        );
+
+        stats.total_bytes = 0;
+        stats.eta_secs = None;
+        assert_eq!(
+            progress_line(&stats),
+            "[1/2]   0.0%  1.0 MiB / 0.0 B  512.0 KiB/s  剩余 --:--:--"
+        );
+    }
+}
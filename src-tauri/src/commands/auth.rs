@@ -1,5 +1,7 @@
 use crate::AppData;
 use crate::models::User;
+use crate::services::auth_service;
+use biliup::bilibili::BiliBili;
 use serde::{Deserialize, Serialize};
 use tauri::Manager;
 use tokio::sync::Mutex;
@@ -39,22 +41,8 @@ pub async fn check_qr_login(app: tauri::AppHandle) -> Result<LoginResponse, Stri
         .map_err(|e| format!("二维码登录状态失败: {e}"))?;
 
     let proxy = app_data.auth_service.get_proxy();
-    app_data.config.lock().await.new_user_config(
-        user.uid,
-        user.username.clone(),
-        bilibili.login_info.clone(),
-        proxy,
-    );
-
-    app_data.clients.lock().await.insert(
-        user.uid,
-        crate::MyClient {
-            bilibili,
-            user: user.clone(),
-        },
-    );
+    register_user(&mut app_data, bilibili, &user, proxy).await;
     app_data.auth_service.destroy();
-
     info!("用户：{} - {} 通过二维码登录成功", user.uid, user.username);
 
     Ok(LoginResponse {
@@ -63,19 +51,55 @@ pub async fn check_qr_login(app: tauri::AppHandle) -> Result<LoginResponse, Stri
     })
 }
 
-/// Cookie 登录
+/// Cookie 登录，cookie 可以是请求头、浏览器导出的 JSON、cookies.txt 的内容或文件路径
 #[tauri::command]
 pub async fn login_with_cookie(
-    _cookie: String,
-    _proxy: Option<String>,
+    app: tauri::AppHandle,
+    cookie: String,
+    proxy: Option<String>,
 ) -> Result<LoginResponse, String> {
-    // TODO: 实现 Cookie 登录逻辑
+    // 校验 Cookie 需要请求接口，完成后再加锁保存
+    let (bilibili, user) = auth_service::cookie_login(&cookie, proxy.as_deref())
+        .await
+        .map_err(|e| format!("Cookie 登录失败: {e}"))?;
+
+    let app_lock = app.state::<Mutex<AppData>>();
+    let mut app_data = app_lock.lock().await;
+    register_user(&mut app_data, bilibili, &user, proxy).await;
+    info!(
+        "用户：{} - {} 通过 Cookie 登录成功",
+        user.uid, user.username
+    );
+
     Ok(LoginResponse {
         success: true,
         message: "登录成功".to_string(),
     })
 }
 
+/// 保存登录信息并加入已登录的客户端
+async fn register_user(
+    app_data: &mut AppData,
+    bilibili: BiliBili,
+    user: &User,
+    proxy: Option<String>,
+) {
+    app_data.config.lock().await.new_user_config(
+        user.uid,
+        user.username.clone(),
+        bilibili.login_info.clone(),
+        proxy,
+    );
+
+    app_data.clients.lock().await.insert(
+        user.uid,
+        crate::MyClient {
+            bilibili,
+            user: user.clone(),
+        },
+    );
+}
+
 /// 退出登录
 #[tauri::command]
 pub async fn logout_user(app: tauri::AppHandle, uid: u64) -> Result<bool, String> {
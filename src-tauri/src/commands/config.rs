@@ -4,7 +4,14 @@ use tauri::Manager;
 use tokio::sync::Mutex;
 use tracing::info;
 
-use crate::{AppData, models::ConfigRoot};
+use crate::{
+    AppData,
+    models::{
+        ApiConfig, ConfigRoot, ProbeConfig, RetryPolicy, ScheduleRule, TranslationProvider,
+        WatchConfig, WebhookConfig,
+    },
+    services::{folder_watcher, http_api},
+};
 use crate::{models::TemplateConfig, utils::get_config_json_path};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -46,6 +53,7 @@ pub async fn save_user_config(
     limit: u32,
     watermark: u8,
     auto_edit: u8,
+    max_concurrent: Option<u32>,
 ) -> Result<bool, String> {
     let data = app.state::<Mutex<AppData>>();
     info!("û({uid})ѱ");
@@ -55,7 +63,15 @@ pub async fn save_user_config(
         .config
         .lock()
         .await
-        .save_user_config(uid, line, proxy, limit, watermark, auto_edit)
+        .save_user_config(
+            uid,
+            line,
+            proxy,
+            limit,
+            watermark,
+            auto_edit,
+            max_concurrent,
+        )
         .map_err(|e| format!("ûʧ: {e}"))?;
     Ok(true)
 }
@@ -72,12 +88,16 @@ pub async fn save_global_config(
     translation_model: String,
     translation_prompt: String,
     translation_auto: bool,
+    translation_provider: Option<TranslationProvider>,
+    translation_auto_parts: Option<bool>,
 ) -> Result<bool, String> {
     let data = app.state::<Mutex<AppData>>();
 
     info!("ȫѱ");
 
-    data.lock().await.config.lock().await.save_global_config(
+    let data_lock = data.lock().await;
+    let mut config = data_lock.config.lock().await;
+    config.save_global_config(
         max_curr,
         auto_start,
         auto_upload,
@@ -88,12 +108,119 @@ pub async fn save_global_config(
         translation_prompt,
         translation_auto,
     );
+    if let Some(provider) = translation_provider {
+        config.translation_provider = provider;
+    }
+    if let Some(auto_parts) = translation_auto_parts {
+        config.translation_auto_parts = auto_parts;
+    }
+    drop(config);
 
-    data.lock()
+    data_lock.upload_service.set_max_concurrent(max_curr).await;
+    Ok(true)
+}
+
+/// 设置上传带宽上限，uid 为空时设置全局上限，立即对运行中的任务生效
+#[tauri::command]
+pub async fn set_bandwidth_limit(
+    app: AppHandle,
+    uid: Option<u64>,
+    bytes_per_sec: u64,
+) -> Result<bool, String> {
+    let data = app.state::<Mutex<AppData>>();
+    let app_data = data.lock().await;
+
+    app_data
+        .config
+        .lock()
+        .await
+        .set_bandwidth_limit(uid, bytes_per_sec)
+        .map_err(|e| format!("设置带宽限制失败: {e}"))?;
+
+    let bandwidth = app_data.upload_service.bandwidth();
+    match uid {
+        Some(uid) => bandwidth.set_account(uid, bytes_per_sec),
+        None => bandwidth.set_global(bytes_per_sec),
+    }
+    info!("带宽限制已更新: uid={:?}, {} B/s", uid, bytes_per_sec);
+    Ok(true)
+}
+
+/// 保存按时段生效的并发数和带宽规则
+#[tauri::command]
+pub async fn save_schedule(app: AppHandle, schedule: Vec<ScheduleRule>) -> Result<bool, String> {
+    let data = app.state::<Mutex<AppData>>();
+    info!("时段规则已更新: {} 条", schedule.len());
+
+    data.lock().await.config.lock().await.schedule = schedule;
+    Ok(true)
+}
+
+/// 保存上传失败后的自动重试策略
+#[tauri::command]
+pub async fn save_retry_policy(app: AppHandle, retry: RetryPolicy) -> Result<bool, String> {
+    let data = app.state::<Mutex<AppData>>();
+    info!(
+        "自动重试策略已更新: 最多 {} 次, 间隔 {}-{} 秒",
+        retry.max_attempts, retry.base_delay_secs, retry.max_delay_secs
+    );
+
+    data.lock().await.config.lock().await.retry = retry;
+    Ok(true)
+}
+
+/// 保存创建任务时的视频检查设置
+#[tauri::command]
+pub async fn save_probe_config(app: AppHandle, probe: ProbeConfig) -> Result<bool, String> {
+    let data = app.state::<Mutex<AppData>>();
+    info!("视频检查设置已更新: {:?}", probe);
+
+    data.lock().await.config.lock().await.probe = probe;
+    Ok(true)
+}
+
+/// 保存本地 HTTP 接口设置并按新设置重启接口，返回包含令牌的设置
+#[tauri::command]
+pub async fn save_api_config(app: AppHandle, api: ApiConfig) -> Result<ApiConfig, String> {
+    {
+        let data = app.state::<Mutex<AppData>>();
+        let data = data.lock().await;
+        let mut config = data.config.lock().await;
+        info!(
+            "本地接口设置已更新: 开启 {}, 端口 {}",
+            api.enabled, api.port
+        );
+        config.api = api;
+    }
+    http_api::restart(app.clone())
+        .await
+        .map_err(|e| format!("启动本地接口失败: {e}"))?;
+
+    let data = app.state::<Mutex<AppData>>();
+    Ok(data.lock().await.config.lock().await.api.clone())
+}
+
+/// 保存 webhook 通知地址，之后的事件立即按新设置发送
+#[tauri::command]
+pub async fn save_webhooks(app: AppHandle, webhooks: Vec<WebhookConfig>) -> Result<bool, String> {
+    let data = app.state::<Mutex<AppData>>();
+    info!("webhook 设置已更新: {} 个", webhooks.len());
+
+    data.lock().await.config.lock().await.webhooks = webhooks;
+    Ok(true)
+}
+
+/// 保存文件夹监控设置并按新设置重启监控
+#[tauri::command]
+pub async fn save_watches(app: AppHandle, watches: Vec<WatchConfig>) -> Result<bool, String> {
+    {
+        let data = app.state::<Mutex<AppData>>();
+        info!("文件夹监控设置已更新: {} 个", watches.len());
+        data.lock().await.config.lock().await.watches = watches;
+    }
+    folder_watcher::restart(app)
         .await
-        .upload_service
-        .set_max_concurrent(max_curr)
-        .await;
+        .map_err(|e| format!("启动文件夹监控失败: {e}"))?;
     Ok(true)
 }
 
@@ -1,14 +1,23 @@
-use std::sync::Arc;
+use std::{collections::HashMap, path::Path, sync::Arc};
 
 use crate::{
     AppData,
-    models::{TemplateConfig, UploadTask, VideoInfo},
+    models::{
+        Draft, LedgerQuery, PublishAction, PublishRecord, QueueStats, SourceFingerprint,
+        TemplateConfig, UploadGroup, UploadTask, VideoInfo,
+    },
+    services::{
+        media_probe, publish_ledger,
+        template_render::{self, TemplatePreview},
+        template_validator::{self, ValidationReport},
+        upload_service::{AccountBenchmark, benchmark_accounts},
+    },
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tauri::{AppHandle, Manager};
 use tokio::sync::Mutex;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UploadProgress {
@@ -96,9 +105,25 @@ pub async fn create_upload_task(
     app: AppHandle,
     uid: u64,
     template: String,
-    video: VideoInfo,
+    mut video: VideoInfo,
 ) -> Result<(), String> {
     let app_lock = app.state::<Mutex<AppData>>();
+    // ffprobe 和翻译可能耗时较长，期间不持有锁
+    let (config, translation) = {
+        let app_data = app_lock.lock().await;
+        let config = app_data.config.lock().await.clone();
+        (config, app_data.translation.clone())
+    };
+    video.probe = media_probe::check(&video.path, &config.probe).await?;
+    translation.auto_translate(&config, &mut video).await;
+    let source = match SourceFingerprint::read_async(&video.path).await {
+        Ok(source) => Some(source),
+        Err(e) => {
+            warn!("读取源文件失败: {} - {}", video.path, e);
+            None
+        }
+    };
+
     let mut app_data = app_lock.lock().await;
     let user = app_data
         .clients
@@ -113,7 +138,7 @@ pub async fn create_upload_task(
     let upload_service = &mut app_data.upload_service;
 
     upload_service
-        .create_task(&user, &template, &video, config_copy, clients_copy)
+        .create_task(&user, &template, &video, source, config_copy, clients_copy)
         .await
         .map_err(|e| e.to_string())?;
 
@@ -159,6 +184,66 @@ pub async fn cancel_upload(app: AppHandle, task_id: String) -> Result<bool, Stri
         .map_err(|e| e.to_string())
 }
 
+/// 将任务移动到队列中的指定位置
+#[tauri::command]
+pub async fn move_upload_task(
+    app: AppHandle,
+    task_id: String,
+    index: usize,
+) -> Result<bool, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+
+    app_data
+        .upload_service
+        .move_task(&task_id, index)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 将任务移动到队列顶部
+#[tauri::command]
+pub async fn move_upload_task_to_top(app: AppHandle, task_id: String) -> Result<bool, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+
+    app_data
+        .upload_service
+        .move_task_to_top(&task_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 将任务移动到队列底部
+#[tauri::command]
+pub async fn move_upload_task_to_bottom(app: AppHandle, task_id: String) -> Result<bool, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+
+    app_data
+        .upload_service
+        .move_task_to_bottom(&task_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 设置任务优先级
+#[tauri::command]
+pub async fn set_upload_priority(
+    app: AppHandle,
+    task_id: String,
+    priority: i32,
+) -> Result<bool, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+
+    app_data
+        .upload_service
+        .set_task_priority(&task_id, priority)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 /// 获取上传队列
 #[tauri::command]
 pub async fn get_upload_queue(app: AppHandle) -> Result<Vec<UploadTask>, String> {
@@ -171,6 +256,114 @@ pub async fn get_upload_queue(app: AppHandle) -> Result<Vec<UploadTask>, String>
         .map_err(|e| e.to_string())
 }
 
+/// 获取整个队列的汇总进度和速率
+#[tauri::command]
+pub async fn get_upload_stats(app: AppHandle) -> Result<QueueStats, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+    Ok(app_data.upload_service.get_queue_stats().await)
+}
+
+/// 获取多分P分组的汇总状态和进度
+#[tauri::command]
+pub async fn get_upload_groups(app: AppHandle) -> Result<Vec<UploadGroup>, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+    Ok(app_data.upload_service.get_upload_groups().await)
+}
+
+/// 对已登录的账号测试各上传线路，uid 为空时测试全部账号
+#[tauri::command]
+pub async fn benchmark_lines(
+    app: AppHandle,
+    uid: Option<u64>,
+) -> Result<Vec<AccountBenchmark>, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    // 测速耗时较长，取出需要的数据后释放锁
+    let (accounts, cache) = {
+        let app_data = app_lock.lock().await;
+        let config = app_data.config.lock().await;
+        let accounts: Vec<_> = app_data
+            .clients
+            .lock()
+            .await
+            .keys()
+            .filter(|id| uid.is_none_or(|uid| uid == **id))
+            .map(|id| (*id, config.config.get(id).and_then(|c| c.proxy.clone())))
+            .collect();
+        (accounts, app_data.upload_service.line_benchmarks())
+    };
+    if accounts.is_empty() {
+        return Err("用户未登录或不存在".to_string());
+    }
+
+    Ok(benchmark_accounts(&cache, accounts).await)
+}
+
+/// 登记一次投稿，分P全部上传完成后由后台自动投稿
+#[tauri::command]
+pub async fn create_draft(
+    app: AppHandle,
+    uid: u64,
+    template: String,
+    task_ids: Vec<String>,
+) -> Result<Draft, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+    app_data
+        .upload_service
+        .create_draft(uid, &template, task_ids)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 向已发布的稿件追加分P，任务全部上传完成后由后台合并分P并编辑稿件
+#[tauri::command]
+pub async fn append_parts(
+    app: AppHandle,
+    uid: u64,
+    aid: u64,
+    task_ids: Vec<String>,
+) -> Result<Draft, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+    app_data
+        .upload_service
+        .append_parts(uid, aid, task_ids)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 获取自动投稿的草稿及投稿结果
+#[tauri::command]
+pub async fn get_drafts(app: AppHandle) -> Result<Vec<Draft>, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+    Ok(app_data.upload_service.submit_pipeline().list().await)
+}
+
+/// 删除草稿，正在等待的草稿不再自动投稿
+#[tauri::command]
+pub async fn remove_draft(app: AppHandle, draft_id: String) -> Result<bool, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+    Ok(app_data
+        .upload_service
+        .submit_pipeline()
+        .remove(&draft_id)
+        .await
+        .is_some())
+}
+
+/// 获取缓存的线路测速结果
+#[tauri::command]
+pub async fn get_line_benchmarks(app: AppHandle) -> Result<Vec<AccountBenchmark>, String> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let cache = app_lock.lock().await.upload_service.line_benchmarks();
+    let benchmarks = cache.lock().await.values().cloned().collect();
+    Ok(benchmarks)
+}
+
 /// 重新上传失败的任务
 #[tauri::command]
 pub async fn retry_upload(app: AppHandle, task_id: String) -> Result<bool, String> {
@@ -184,12 +377,102 @@ pub async fn retry_upload(app: AppHandle, task_id: String) -> Result<bool, Strin
         .map_err(|e| e.to_string())
 }
 
+/// 按投稿接口的限制检查模板，返回字段级的错误、警告和自动修正后的模板
+#[tauri::command]
+pub async fn validate_template(template: TemplateConfig) -> Result<ValidationReport, String> {
+    Ok(template_validator::validate(&template))
+}
+
+/// 按翻译配置翻译标题，接口密钥只在后端使用
+#[tauri::command]
+pub async fn translate_title(app: AppHandle, text: String) -> Result<String, String> {
+    let (config, translation) = {
+        let app_lock = app.state::<Mutex<AppData>>();
+        let app_data = app_lock.lock().await;
+        let config = app_data.config.lock().await.clone();
+        (config, app_data.translation.clone())
+    };
+    translation
+        .translate(&config, &text)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 用示例文件渲染模板中的变量，path 为空时使用模板中已有的分P
+#[tauri::command]
+pub async fn preview_template(
+    app: AppHandle,
+    uid: u64,
+    mut template: TemplateConfig,
+    path: Option<String>,
+) -> Result<TemplatePreview, String> {
+    if let Some(path) = path.filter(|path| !path.is_empty()) {
+        let mut video = VideoInfo::from_path("preview".to_string(), Path::new(&path));
+        video.probe = media_probe::probe(&path).await.unwrap_or_else(|e| {
+            warn!("读取示例文件信息失败: {}", e);
+            None
+        });
+        template.videos = vec![video];
+    }
+    let account = account_name(&app, uid).await;
+    template_render::apply(&mut template, &account, &HashMap::new()).map_err(|e| e.to_string())?;
+    Ok(TemplatePreview::new(&template))
+}
+
+async fn account_name(app: &AppHandle, uid: u64) -> String {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+    app_data
+        .config
+        .lock()
+        .await
+        .config
+        .get(&uid)
+        .map(|user_config| user_config.user.name.clone())
+        .unwrap_or_default()
+}
+
 #[tauri::command]
-pub async fn submit(app: AppHandle, uid: u64, form: TemplateConfig) -> Result<Value, String> {
+pub async fn submit(
+    app: AppHandle,
+    uid: u64,
+    mut form: TemplateConfig,
+    template: Option<String>,
+) -> Result<Value, String> {
+    let account = account_name(&app, uid).await;
+    let task_ids: Vec<String> = form.videos.iter().map(|v| v.id.clone()).collect();
+    let sources = {
+        let app_lock = app.state::<Mutex<AppData>>();
+        let app_data = app_lock.lock().await;
+        app_data.upload_service.sources(&task_ids).await
+    };
+    template_render::apply(&mut form, &account, &sources).map_err(|e| e.to_string())?;
+    let report = template_validator::validate(&form);
+    for warning in &report.warnings {
+        warn!("投稿信息检查: {} - {}", warning.field, warning.message);
+    }
+    report.ensure_valid().map_err(|e| e.to_string())?;
+    // 提交检查过的修正后的表单
+    let form = report.fixed;
+
     let app_lock = app.state::<Mutex<AppData>>();
     let app_data = app_lock.lock().await;
 
-    if form.aid.is_none() {
+    app_data
+        .upload_service
+        .verify_sources(&task_ids)
+        .await
+        .map_err(|e| e.to_string())?;
+    app_data
+        .upload_service
+        .verify_groups(&task_ids)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let template = template.unwrap_or_default();
+    let videos = form.videos.clone();
+    let aid = form.aid;
+    let result = if aid.is_none() {
         // 将前端表单转换为B站API需要的格式
         let bilibili_form = form.into_bilibili_form();
         let studio = bilibili_form.try_into_studio().map_err(|e| e.to_string())?;
@@ -222,7 +505,18 @@ pub async fn submit(app: AppHandle, uid: u64, form: TemplateConfig) -> Result<Va
         {
             Ok(resp) => {
                 info!("添加稿件成功：{resp}");
-                Ok(resp.data.ok_or("返回值错误").map_err(|e| e.to_string())?)
+                let data = resp.data.ok_or("返回值错误").map_err(|e| e.to_string())?;
+                publish_ledger::record(
+                    uid,
+                    &template,
+                    PublishAction::Submit,
+                    &videos,
+                    &sources,
+                    &studio,
+                    &data,
+                )
+                .await;
+                Ok(data)
             }
             Err(e) => Err(e.to_string()),
         }
@@ -240,7 +534,23 @@ pub async fn submit(app: AppHandle, uid: u64, form: TemplateConfig) -> Result<Va
         match bilibili.edit_by_web(&studio).await {
             Ok(resp) => {
                 info!("编辑稿件成功：{resp}");
-                Ok(resp["data"].clone())
+                let mut data = resp["data"].clone();
+                if data["aid"].is_null()
+                    && let Some(aid) = aid
+                {
+                    data["aid"] = aid.into();
+                }
+                publish_ledger::record(
+                    uid,
+                    &template,
+                    PublishAction::Edit,
+                    &videos,
+                    &sources,
+                    &studio,
+                    &data,
+                )
+                .await;
+                Ok(data)
             }
             Err(e) => {
                 let err_text = e.to_string();
@@ -250,5 +560,42 @@ pub async fn submit(app: AppHandle, uid: u64, form: TemplateConfig) -> Result<Va
                 Err(err_text)
             }
         }
-    }
+    };
+    app_data
+        .upload_service
+        .events()
+        .submit_result(uid, &template, &result);
+    result
+}
+
+/// 按日期、账号、模板或文件路径查询投稿账本
+#[tauri::command]
+pub async fn query_publish_ledger(query: LedgerQuery) -> Result<Vec<PublishRecord>, String> {
+    tokio::task::spawn_blocking(move || publish_ledger::query(&query))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// 把投稿账本导出为 CSV，未指定路径时保存到配置目录，返回导出文件路径
+#[tauri::command]
+pub async fn export_publish_ledger(
+    query: LedgerQuery,
+    path: Option<String>,
+) -> Result<String, String> {
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => {
+            let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
+            crate::utils::get_config_dir()
+                .map_err(|e| e.to_string())?
+                .join(format!("publish_ledger_{timestamp}.csv"))
+        }
+    };
+    let export_path = path.clone();
+    tokio::task::spawn_blocking(move || publish_ledger::export_csv(&query, &export_path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("导出投稿账本失败: {e}"))?;
+    Ok(path.to_string_lossy().to_string())
 }
@@ -1,7 +1,6 @@
+use crate::utils::ffmpeg;
 use serde::Deserialize;
 use std::path::Path;
-use std::process::Command;
-use tokio::task;
 use tracing::info;
 
 #[derive(Debug, Deserialize)]
@@ -143,24 +142,13 @@ pub async fn edit_video_with_watermark(request: VideoEditRequest) -> Result<Stri
 
     info!("执行视频编辑命令: ffmpeg {}", args.join(" "));
 
-    let output = task::spawn_blocking(move || Command::new("ffmpeg").args(&args).output())
+    let output = ffmpeg::run("ffmpeg", args)
         .await
-        .map_err(|e| format!("执行视频编辑任务失败: {e}"))?
-        .map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                "未找到 ffmpeg，请先安装 ffmpeg 并确保其在系统 PATH 中".to_string()
-            } else {
-                format!("调用 ffmpeg 失败: {e}")
-            }
-        })?;
+        .map_err(|e| ffmpeg::spawn_error("ffmpeg", &e))?;
 
     if !output.status.success() {
-        let err_text = String::from_utf8_lossy(&output.stderr).to_string();
-        let concise = err_text
-            .lines()
-            .rev()
-            .find(|line| !line.trim().is_empty())
-            .unwrap_or("视频导出失败");
+        let concise =
+            ffmpeg::last_error_line(&output).unwrap_or_else(|| "视频导出失败".to_string());
         return Err(format!("视频导出失败: {concise}"));
     }
 
@@ -1,3 +1,4 @@
+pub mod cli;
 mod commands;
 mod models;
 mod services;
@@ -15,8 +16,11 @@ use tracing::{error, info};
 use utils::CompatibilityConverter;
 
 use crate::{
-    models::{ConfigRoot, User},
-    services::{AuthService, upload_service::UploadService},
+    models::{ConfigRoot, User, UserConfig},
+    services::{
+        AuthService, folder_watcher, http_api, translation::TranslationService,
+        upload_service::UploadService, webhook::WebhookService,
+    },
     utils::{crypto::encode_base64, get_config_json_path, get_log_path},
 };
 
@@ -29,54 +33,85 @@ pub struct AppData {
     config: Arc<Mutex<ConfigRoot>>,
     auth_service: AuthService,
     upload_service: UploadService,
+    translation: TranslationService,
     clients: Arc<Mutex<HashMap<u64, MyClient>>>,
+    /// 本地 HTTP 接口的后台任务，未开启时为空
+    api_server: Option<tokio::task::JoinHandle<()>>,
+    /// 文件夹监控的后台任务，没有开启的监控时为空
+    folder_watcher: Option<tokio::task::JoinHandle<()>>,
     // client: StatelessClient,
 }
 
+/// 用保存的登录信息创建客户端，并读取账号信息确认登录有效
+async fn connect(user_config: &UserConfig) -> Result<(u64, MyClient)> {
+    let bilibili = biliup::credential::bilibili_from_info(
+        user_config.user.cookie.clone(),
+        user_config.proxy.as_deref(),
+    )?;
+
+    let myinfo = bilibili.my_info().await?;
+    let username = myinfo["data"]["name"].as_str().unwrap().to_owned();
+    let uid = myinfo["data"]["mid"].as_u64().unwrap_or(0);
+    let avatar_url = myinfo["data"]["face"].as_str().unwrap_or("").to_string();
+
+    let avatar = bilibili
+        .client
+        .get(avatar_url)
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("获取用户头像失败: {}", e))?
+        .bytes()
+        .await?;
+    let avatar = encode_base64(&avatar);
+
+    Ok((
+        uid,
+        MyClient {
+            bilibili,
+            user: User::new(uid, username, avatar),
+        },
+    ))
+}
+
 async fn startup() -> Result<AppData> {
     let config = ConfigRoot::from_file(&get_config_json_path()?)?;
     let mut clients = HashMap::new();
 
     for user_config in config.config.values() {
-        let bilibili = biliup::credential::bilibili_from_info(
-            user_config.user.cookie.clone(),
-            user_config.proxy.as_deref(),
-        )?;
-
-        let myinfo = bilibili.my_info().await?;
-        let username = myinfo["data"]["name"].as_str().unwrap().to_owned();
-        let uid = myinfo["data"]["mid"].as_u64().unwrap_or(0);
-        let avatar_url = myinfo["data"]["face"].as_str().unwrap_or("").to_string();
-
-        let avatar = bilibili
-            .client
-            .get(avatar_url)
-            .send()
-            .await
-            .map_err(|e| anyhow::anyhow!("获取用户头像失败: {}", e))?
-            .bytes()
-            .await?;
-        let avatar = encode_base64(&avatar);
-
-        clients.insert(
-            uid,
-            MyClient {
-                bilibili,
-                user: User::new(uid, username, avatar),
-            },
-        );
+        let (uid, client) = connect(user_config).await?;
+        clients.insert(uid, client);
     }
 
     let max_curr = config.max_curr;
+    let config = Arc::new(Mutex::new(config));
+    let clients = Arc::new(Mutex::new(clients));
+    let upload_service = UploadService::new(max_curr, Arc::clone(&config));
+    upload_service
+        .bandwidth()
+        .apply_config(&*config.lock().await);
+    if let Err(e) = upload_service
+        .restore_queue(Arc::clone(&config), Arc::clone(&clients))
+        .await
+    {
+        error!("恢复上传队列失败: {}", e);
+    }
+    if let Err(e) = upload_service.submit_pipeline().restore().await {
+        error!("恢复投稿草稿失败: {}", e);
+    }
+
     Ok(AppData {
-        config: Arc::new(Mutex::new(config)),
+        config,
         auth_service: AuthService::new(),
-        upload_service: UploadService::new(max_curr),
-        clients: Arc::new(Mutex::new(clients)),
+        upload_service,
+        translation: TranslationService::new(),
+        clients,
+        api_server: None,
+        folder_watcher: None,
     })
 }
 
-fn setup_logs(log_level: &str) -> Result<()> {
+/// 初始化日志，console 为 false 时只写入日志文件
+fn setup_logs(log_level: &str, console: bool) -> Result<()> {
     let log_dir = get_log_path()?;
     let log_file = format!(
         "biliup-{}.log",
@@ -119,13 +154,16 @@ fn setup_logs(log_level: &str) -> Result<()> {
     #[cfg(debug_assertions)]
     tracing_subscriber::registry()
         .with(file_layer.with_filter(level))
-        .with(console_layer.with_filter(tracing_subscriber::filter::LevelFilter::TRACE))
+        .with(
+            console
+                .then(|| console_layer.with_filter(tracing_subscriber::filter::LevelFilter::TRACE)),
+        )
         .init();
 
     #[cfg(not(debug_assertions))]
     tracing_subscriber::registry()
         .with(file_layer.with_filter(level))
-        .with(console_layer.with_filter(level))
+        .with(console.then(|| console_layer.with_filter(level)))
         .init();
 
     info!("日志已输出到: {:?} {}", get_log_path()?, log_file);
@@ -144,15 +182,21 @@ pub async fn run() {
         error!("加载配置失败: {}", e);
         let config = ConfigRoot::default();
         let max_curr = config.max_curr;
+        let config = Arc::new(Mutex::new(config));
         AppData {
-            config: Arc::new(Mutex::new(config)),
+            upload_service: UploadService::new(max_curr, Arc::clone(&config)),
+            config,
             auth_service: AuthService::new(),
-            upload_service: UploadService::new(max_curr),
+            translation: TranslationService::new(),
             clients: Arc::new(Mutex::new(HashMap::new())),
+            api_server: None,
+            folder_watcher: None,
         }
     });
 
-    setup_logs(&appdata.config.lock().await.log_level.clone()).expect("日志初始化失败");
+    setup_logs(&appdata.config.lock().await.log_level.clone(), true).expect("日志初始化失败");
+
+    WebhookService::start(Arc::clone(&appdata.config), appdata.upload_service.events());
 
     tauri::Builder::default()
         .plugin(tauri_plugin_http::init())
@@ -170,8 +214,25 @@ pub async fn run() {
                 }
             }
 
+            // 上传进度通过事件推送到前端
+            appdata.upload_service.set_app_handle(app.handle().clone());
+
             // 管理应用数据状态
             app.manage(Mutex::new(appdata));
+
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = http_api::restart(handle).await {
+                    error!("启动本地接口失败: {}", e);
+                }
+            });
+
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = folder_watcher::restart(handle).await {
+                    error!("启动文件夹监控失败: {}", e);
+                }
+            });
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -190,6 +251,23 @@ pub async fn run() {
             pause_upload,
             cancel_upload,
             get_upload_queue,
+            get_upload_stats,
+            get_upload_groups,
+            benchmark_lines,
+            get_line_benchmarks,
+            create_draft,
+            append_parts,
+            validate_template,
+            preview_template,
+            translate_title,
+            query_publish_ledger,
+            export_publish_ledger,
+            get_drafts,
+            remove_draft,
+            move_upload_task,
+            move_upload_task_to_top,
+            move_upload_task_to_bottom,
+            set_upload_priority,
             retry_upload,
             submit,
             // 配置相关命令
@@ -197,6 +275,13 @@ pub async fn run() {
             save_config,
             save_user_config,
             save_global_config,
+            set_bandwidth_limit,
+            save_schedule,
+            save_retry_policy,
+            save_probe_config,
+            save_api_config,
+            save_webhooks,
+            save_watches,
             add_user_template,
             update_user_template,
             delete_user_template,
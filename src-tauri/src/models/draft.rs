@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum DraftStatus {
+    Uploading,  // 等待分P上传完成
+    Submitting, // 投稿中
+    Submitted,  // 已投稿
+    Failed,     // 投稿失败
+}
+
+/// 一次投稿包含的模板和上传任务，分P全部上传完成后由后台自动投稿
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Draft {
+    pub id: String,
+    pub uid: u64,
+    pub template: String,
+    /// 分P对应的上传任务，按投稿时的分P顺序
+    pub task_ids: Vec<String>,
+    pub status: DraftStatus,
+    pub error_message: Option<String>,
+    pub aid: Option<u64>,
+    pub bvid: Option<String>,
+    pub created_at: usize,
+    pub submitted_at: Option<usize>,
+    /// 追加分P的目标稿件，为空时按模板投稿
+    #[serde(default)]
+    pub append_to: Option<u64>,
+}
+
+impl Draft {
+    pub fn new(uid: u64, template: &str, task_ids: Vec<String>) -> Self {
+        let created_at = chrono::Utc::now().timestamp_millis() as usize;
+        Self {
+            id: format!("{uid}-{template}-{created_at}"),
+            uid,
+            template: template.to_string(),
+            task_ids,
+            status: DraftStatus::Uploading,
+            error_message: None,
+            aid: None,
+            bvid: None,
+            created_at,
+            submitted_at: None,
+            append_to: None,
+        }
+    }
+
+    /// 向已发布的稿件追加分P，不使用模板
+    pub fn append(uid: u64, aid: u64, task_ids: Vec<String>) -> Self {
+        let mut draft = Self::new(uid, "", task_ids);
+        draft.id = format!("{uid}-av{aid}-{}", draft.created_at);
+        draft.append_to = Some(aid);
+        draft
+    }
+
+    pub fn is_uploading(&self) -> bool {
+        matches!(self.status, DraftStatus::Uploading)
+    }
+
+    pub fn submitting(&mut self) {
+        self.status = DraftStatus::Submitting;
+        self.error_message = None;
+    }
+
+    pub fn submitted(&mut self, aid: Option<u64>, bvid: Option<String>) {
+        self.status = DraftStatus::Submitted;
+        self.aid = aid;
+        self.bvid = bvid;
+        self.submitted_at = Some(chrono::Utc::now().timestamp_millis() as usize);
+    }
+
+    pub fn fail(&mut self, error_message: String) {
+        self.status = DraftStatus::Failed;
+        self.error_message = Some(error_message);
+    }
+}
@@ -1,8 +1,16 @@
+pub mod draft;
+pub mod publish_record;
 pub mod upload_form;
 pub mod upload_task;
 pub mod user;
 pub mod user_config;
 
+pub use draft::*;
+pub use publish_record::*;
 pub use upload_task::*;
 pub use user::*;
-pub use user_config::{ConfigRoot, Subtitle, TemplateConfig, UserConfig, UserInfo, VideoInfo};
+pub use user_config::{
+    ApiConfig, ConfigRoot, MediaProbe, ProbeConfig, RetryPolicy, ScheduleRule, Subtitle,
+    TemplateConfig, TranslationProvider, UserConfig, UserInfo, VideoInfo, WatchConfig,
+    WebhookConfig,
+};
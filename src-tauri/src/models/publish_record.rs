@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PublishAction {
+    Submit, // 新投稿
+    Edit,   // 编辑稿件
+    Append, // 追加分P
+}
+
+/// 投稿账本中的一个分P
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishedPart {
+    pub title: String,
+    pub filename: String,
+    pub cid: u64,
+    /// 本地文件路径，稿件原有的分P为空
+    #[serde(default)]
+    pub path: String,
+    /// 本地文件的抽样哈希，与上传任务的源文件指纹相同
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+/// 一次成功的投稿或编辑
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishRecord {
+    pub uid: u64,
+    /// 模板名称，直接提交表单或追加分P时为空
+    #[serde(default)]
+    pub template: String,
+    pub action: PublishAction,
+    pub aid: Option<u64>,
+    pub bvid: Option<String>,
+    pub title: String,
+    pub parts: Vec<PublishedPart>,
+    /// 提交给B站的表单
+    pub form: Value,
+    pub response: Value,
+    pub published_at: usize,
+}
+
+/// 查询账本的条件，为空的条件不过滤
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LedgerQuery {
+    /// 起止时间，毫秒时间戳
+    pub since: Option<usize>,
+    pub until: Option<usize>,
+    pub uid: Option<u64>,
+    pub template: Option<String>,
+    /// 匹配任一分P本地路径的片段
+    pub path: Option<String>,
+}
+
+impl LedgerQuery {
+    pub fn matches(&self, record: &PublishRecord) -> bool {
+        self.since.is_none_or(|since| record.published_at >= since)
+            && self.until.is_none_or(|until| record.published_at <= until)
+            && self.uid.is_none_or(|uid| record.uid == uid)
+            && self
+                .template
+                .as_ref()
+                .is_none_or(|template| &record.template == template)
+            && self.path.as_ref().is_none_or(|path| {
+                record
+                    .parts
+                    .iter()
+                    .any(|part| part.path.contains(path.as_str()))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(uid: u64, template: &str, published_at: usize, path: &str) -> PublishRecord {
+        PublishRecord {
+            uid,
+            template: template.to_string(),
+            action: PublishAction::Submit,
+            aid: None,
+            bvid: None,
+            title: String::new(),
+            parts: vec![PublishedPart {
+                title: "P1".to_string(),
+                filename: String::new(),
+                cid: 0,
+                path: path.to_string(),
+                hash: None,
+            }],
+            form: Value::Null,
+            response: Value::Null,
+            published_at,
+        }
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert!(LedgerQuery::default().matches(&record(1, "", 0, "")));
+    }
+
+    #[test]
+    fn query_filters_by_each_condition() {
+        let record = record(42, "daily", 1_000, "/videos/2024/live.mp4");
+
+        let query = |f: fn(&mut LedgerQuery)| {
+            let mut query = LedgerQuery::default();
+            f(&mut query);
+            query.matches(&record)
+        };
+        assert!(query(|q| q.since = Some(1_000)));
+        assert!(!query(|q| q.since = Some(1_001)));
+        assert!(query(|q| q.until = Some(1_000)));
+        assert!(!query(|q| q.until = Some(999)));
+        assert!(query(|q| q.uid = Some(42)));
+        assert!(!query(|q| q.uid = Some(7)));
+        assert!(query(|q| q.template = Some("daily".to_string())));
+        assert!(!query(|q| q.template = Some("dai".to_string())));
+        assert!(query(|q| q.path = Some("2024/live".to_string())));
+        assert!(!query(|q| q.path = Some("2025".to_string())));
+    }
+}
@@ -1,15 +1,23 @@
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
 
 use crate::{
     MyClient,
     models::{ConfigRoot, User, VideoInfo},
-    utils::get_file_size,
+    services::upos::{UploadSession, UploadedPart},
 };
 use debug_ignore::DebugIgnore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tokio::sync::Mutex;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UploadTask {
     pub id: String,
     pub template: String,
@@ -21,15 +29,337 @@ pub struct UploadTask {
     pub started_at: Option<usize>,
     pub finished_at: Option<usize>,
     pub retry_count: u32,
+    /// 优先级，数值越大越先开始
+    #[serde(default)]
+    pub priority: i32,
     pub progress: f64,
     pub total_size: u64,
     pub total_transmit_bytes: u64,
+    /// 最近几秒内的实际发送速率
+    #[serde(default)]
+    pub speed_bytes_per_sec: f64,
+    #[serde(default)]
+    pub eta_secs: Option<u64>,
+    /// 本次运行的平均发送速率
+    #[serde(default)]
+    pub average_speed: f64,
+    /// 断点续传使用的上传会话
+    #[serde(default)]
+    pub session: Option<UploadSession>,
+    /// 创建任务时源文件的状态，上传和投稿前用于确认文件未被改动
+    #[serde(default)]
+    pub source: Option<SourceFingerprint>,
+    /// 最近一次失败的原因
+    #[serde(default)]
+    pub error_kind: Option<ErrorKind>,
+    /// 每次失败的记录
+    #[serde(default)]
+    pub attempts: Vec<UploadAttempt>,
+    /// 本轮已自动重试的次数，手动重试后清零
+    #[serde(default)]
+    pub auto_retries: u32,
+    /// 自动重试的时间，未到时间前任务保持等待
+    #[serde(default)]
+    pub next_retry_at: Option<usize>,
+    /// 暂停的原因，按时段规则暂停的任务在并发名额空出后自动继续
+    #[serde(default)]
+    pub pause_reason: Option<PauseReason>,
     #[serde(skip)]
-    pub config: Arc<Mutex<ConfigRoot>>,
+    speed_meter: SpeedMeter,
+    /// 每次暂停加一，暂停超时的计时器据此判断期间是否已继续或再次暂停
     #[serde(skip)]
+    pause_generation: u64,
+    #[serde(skip, default = "detached_config")]
+    pub config: Arc<Mutex<ConfigRoot>>,
+    #[serde(skip, default = "detached_clients")]
     pub clients: DebugIgnore<Arc<Mutex<HashMap<u64, MyClient>>>>,
 }
 
+/// 反序列化时的占位配置，需通过 `UploadTask::attach` 替换
+fn detached_config() -> Arc<Mutex<ConfigRoot>> {
+    Arc::new(Mutex::new(ConfigRoot::default()))
+}
+
+fn detached_clients() -> DebugIgnore<Arc<Mutex<HashMap<u64, MyClient>>>> {
+    DebugIgnore(Arc::new(Mutex::new(HashMap::new())))
+}
+
+/// 计算速率的滑动窗口长度
+const SPEED_WINDOW: Duration = Duration::from_secs(5);
+
+/// 基于滑动窗口统计实际发送的字节数，重传的数据同样计入
+#[derive(Debug, Clone, Default)]
+struct SpeedMeter {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl SpeedMeter {
+    fn record(&mut self, bytes: u64) {
+        self.record_at(Instant::now(), bytes);
+    }
+
+    fn record_at(&mut self, at: Instant, bytes: u64) {
+        self.samples.push_back((at, bytes));
+        self.prune(at);
+    }
+
+    fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while self
+            .samples
+            .front()
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) > SPEED_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    fn speed(&mut self) -> f64 {
+        self.speed_at(Instant::now())
+    }
+
+    fn speed_at(&mut self, now: Instant) -> f64 {
+        self.prune(now);
+        let Some((oldest, _)) = self.samples.front() else {
+            return 0.0;
+        };
+        // 刚开始上传时窗口未填满，按实际经过的时间计算
+        let window = now
+            .saturating_duration_since(*oldest)
+            .clamp(Duration::from_secs(1), SPEED_WINDOW);
+        let bytes: u64 = self.samples.iter().map(|(_, bytes)| bytes).sum();
+        bytes as f64 / window.as_secs_f64()
+    }
+}
+
+/// 整个队列的汇总信息
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueueStats {
+    pub total: usize,
+    pub waiting: usize,
+    pub pending: usize,
+    pub running: usize,
+    pub paused: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub total_bytes: u64,
+    pub uploaded_bytes: u64,
+    pub speed_bytes_per_sec: f64,
+    pub eta_secs: Option<u64>,
+}
+
+impl QueueStats {
+    pub fn add(&mut self, task: &UploadTask) {
+        self.total += 1;
+        match task.status {
+            TaskStatus::Waiting => self.waiting += 1,
+            TaskStatus::Pending => self.pending += 1,
+            TaskStatus::Running => self.running += 1,
+            TaskStatus::Paused => self.paused += 1,
+            TaskStatus::Completed => self.completed += 1,
+            TaskStatus::Failed => self.failed += 1,
+            TaskStatus::Cancelled => {}
+        }
+        self.total_bytes += task.total_size;
+        self.uploaded_bytes += task.uploaded_bytes();
+        self.speed_bytes_per_sec += task.speed_bytes_per_sec;
+    }
+
+    /// 按当前总速率估算剩余未完成任务所需时间
+    pub fn finish(mut self) -> Self {
+        let remaining = self.total_bytes.saturating_sub(self.uploaded_bytes);
+        self.eta_secs = (self.speed_bytes_per_sec > 0.0)
+            .then(|| (remaining as f64 / self.speed_bytes_per_sec).ceil() as u64);
+        self
+    }
+}
+
+/// 任务暂停的原因
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PauseReason {
+    User,     // 手动暂停
+    Schedule, // 超出时段规则的并发数
+}
+
+/// 上传失败的原因分类，决定是否自动重试
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ErrorKind {
+    Network,        // 网络错误或超时
+    Server,         // 服务器错误
+    SessionExpired, // 上传会话失效
+    Auth,           // 未登录或登录失效
+    SourceMissing,  // 源文件不存在或无法读取
+    SourceChanged,  // 源文件在创建任务后被改动
+    GroupFailed,    // 同组的其他分P上传失败
+    Unknown,        // 其他错误
+}
+
+impl ErrorKind {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Network | Self::Server | Self::SessionExpired)
+    }
+}
+
+/// 部分哈希每段读取的长度
+const SAMPLE_SIZE: u64 = 1 << 20;
+
+/// 源文件的大小、修改时间和头中尾三段内容的哈希
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SourceFingerprint {
+    pub path: String,
+    pub size: u64,
+    pub mtime: u64,
+    pub partial_hash: String,
+}
+
+impl SourceFingerprint {
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let size = metadata.len();
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        let mut hasher = Sha256::new();
+        hasher.update(size.to_le_bytes());
+        let mut buf = vec![0; SAMPLE_SIZE as usize];
+        for offset in [
+            0,
+            size.saturating_sub(SAMPLE_SIZE) / 2,
+            size.saturating_sub(SAMPLE_SIZE),
+        ] {
+            file.seek(SeekFrom::Start(offset))?;
+            let len = SAMPLE_SIZE.min(size - offset) as usize;
+            file.read_exact(&mut buf[..len])?;
+            hasher.update(&buf[..len]);
+        }
+
+        Ok(Self {
+            path: path.to_string_lossy().to_string(),
+            size,
+            mtime,
+            partial_hash: format!("{:x}", hasher.finalize()),
+        })
+    }
+
+    /// 在阻塞线程池中读取，避免在异步任务或持有锁时读文件
+    pub async fn read_async(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        tokio::task::spawn_blocking(move || Self::read(&path))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    /// 与当前磁盘上的文件比较，返回变化的说明
+    pub fn diff(&self, current: &Self) -> Option<String> {
+        if self.size != current.size {
+            Some(format!("大小 {} -> {}", self.size, current.size))
+        } else if self.mtime != current.mtime {
+            Some("修改时间变化".to_string())
+        } else if self.partial_hash != current.partial_hash {
+            Some("文件内容变化".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// 一次失败的上传尝试
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadAttempt {
+    pub started_at: Option<usize>,
+    pub failed_at: usize,
+    pub line: Option<String>,
+    pub kind: ErrorKind,
+    pub error: String,
+}
+
+/// 同一稿件的多个分P，作为整体开始、失败和重试
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadGroup {
+    pub id: String,
+    pub uid: u64,
+    pub template: String,
+    pub group_key: String,
+    /// 分P的任务ID，按分P顺序
+    pub task_ids: Vec<String>,
+    pub roles: Vec<String>,
+    pub status: TaskStatus,
+    pub progress: f64,
+    pub total_size: u64,
+    pub uploaded_bytes: u64,
+    pub speed_bytes_per_sec: f64,
+    pub eta_secs: Option<u64>,
+    #[serde(skip)]
+    statuses: Vec<TaskStatus>,
+}
+
+impl UploadGroup {
+    pub fn new(id: String, task: &UploadTask) -> Self {
+        Self {
+            id,
+            uid: task.user.uid,
+            template: task.template.clone(),
+            group_key: task.video.group_key.clone(),
+            task_ids: Vec::new(),
+            roles: Vec::new(),
+            status: TaskStatus::Waiting,
+            progress: 0.0,
+            total_size: 0,
+            uploaded_bytes: 0,
+            speed_bytes_per_sec: 0.0,
+            eta_secs: None,
+            statuses: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, task: &UploadTask) {
+        self.task_ids.push(task.id.clone());
+        self.roles.push(task.video.group_role.clone());
+        self.statuses.push(task.status.clone());
+        self.total_size += task.total_size;
+        self.uploaded_bytes += task.uploaded_bytes();
+        self.speed_bytes_per_sec += task.speed_bytes_per_sec;
+    }
+
+    /// 汇总分P的状态和进度，任一分P失败时整组视为失败
+    pub fn finish(mut self) -> Self {
+        let any = |f: fn(&TaskStatus) -> bool| self.statuses.iter().any(f);
+        self.status = if any(|s| matches!(s, TaskStatus::Failed)) {
+            TaskStatus::Failed
+        } else if self
+            .statuses
+            .iter()
+            .all(|s| matches!(s, TaskStatus::Completed))
+        {
+            TaskStatus::Completed
+        } else if any(|s| matches!(s, TaskStatus::Running)) {
+            TaskStatus::Running
+        } else if any(|s| matches!(s, TaskStatus::Pending)) {
+            TaskStatus::Pending
+        } else if any(|s| matches!(s, TaskStatus::Paused)) {
+            TaskStatus::Paused
+        } else {
+            TaskStatus::Waiting
+        };
+        self.progress = if self.total_size > 0 {
+            (self.uploaded_bytes as f64 / self.total_size as f64 * 100.0).clamp(0.0, 100.0)
+        } else {
+            0.0
+        };
+        let remaining = self.total_size.saturating_sub(self.uploaded_bytes);
+        self.eta_secs = (self.speed_bytes_per_sec > 0.0)
+            .then(|| (remaining as f64 / self.speed_bytes_per_sec).ceil() as u64);
+        self
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TaskStatus {
     Waiting,   // 未开始
@@ -42,10 +372,12 @@ pub enum TaskStatus {
 }
 
 impl UploadTask {
+    /// source 为调用方事先读取的源文件指纹，读取失败时为空
     pub fn new(
         user: &User,
         template: &str,
         video: &VideoInfo,
+        source: Option<SourceFingerprint>,
         config: Arc<Mutex<ConfigRoot>>,
         clients: Arc<Mutex<HashMap<u64, MyClient>>>,
     ) -> Self {
@@ -60,49 +392,127 @@ impl UploadTask {
             started_at: None,
             finished_at: None,
             retry_count: 0,
+            priority: 0,
             progress: 0.0,
-            total_size: get_file_size(Path::new(&video.path)).unwrap_or(0),
+            total_size: source.as_ref().map_or(0, |source| source.size),
             total_transmit_bytes: 0,
+            speed_bytes_per_sec: 0.0,
+            eta_secs: None,
+            average_speed: 0.0,
+            session: None,
+            source,
+            error_kind: None,
+            attempts: Vec::new(),
+            auto_retries: 0,
+            next_retry_at: None,
+            pause_reason: None,
+            speed_meter: SpeedMeter::default(),
+            pause_generation: 0,
             config,
             clients: DebugIgnore(clients),
         }
     }
 
+    /// 从磁盘恢复任务后重新绑定共享的配置和客户端
+    pub fn attach(
+        &mut self,
+        config: Arc<Mutex<ConfigRoot>>,
+        clients: Arc<Mutex<HashMap<u64, MyClient>>>,
+    ) {
+        self.config = config;
+        self.clients = DebugIgnore(clients);
+    }
+
+    /// 修正恢复后的任务状态，上次退出时运行中的任务不会自动继续传输
+    pub fn restore(&mut self, auto_start: bool) {
+        if self.is_running() {
+            self.status = if auto_start {
+                TaskStatus::Pending
+            } else {
+                TaskStatus::Paused
+            };
+        }
+        if !self.is_completed() {
+            // 进度以会话中已确认的分片为准
+            self.progress = self.session_progress();
+        }
+        self.total_transmit_bytes = 0;
+        self.refresh_speed();
+    }
+
     pub fn title(&self) -> String {
         format!("{} - {}", self.user.username, self.video.title)
     }
 
+    /// 多分P分组的标识，同一账号同一模板下 group_key 相同的任务属于同一组
+    pub fn group_id(&self) -> Option<String> {
+        let key = self.video.group_key.trim();
+        (!key.is_empty()).then(|| format!("{}-{}-{}", self.user.uid, self.template, key))
+    }
+
     pub fn start(&mut self) {
         self.status = TaskStatus::Running;
         self.total_transmit_bytes = 0;
+        self.speed_meter.reset();
         self.started_at = Some(chrono::Utc::now().timestamp_millis() as usize);
+        self.refresh_speed();
     }
 
     pub fn pending(&mut self) {
         self.status = TaskStatus::Pending;
+        self.next_retry_at = None;
+        self.pause_reason = None;
+        self.refresh_speed();
     }
 
-    pub fn pause(&mut self) {
+    /// 暂停并返回本次暂停的编号
+    pub fn pause(&mut self) -> u64 {
         self.status = TaskStatus::Paused;
+        self.pause_reason = Some(PauseReason::User);
+        self.pause_generation += 1;
+        self.refresh_speed();
+        self.pause_generation
+    }
+
+    /// 仍处于编号为 generation 的那次暂停中
+    pub fn is_paused_since(&self, generation: u64) -> bool {
+        self.is_paused() && self.pause_generation == generation
+    }
+
+    /// 超出当前时段的并发数时暂停，保留上传会话，继续时从未完成的分片开始
+    pub fn pause_for_schedule(&mut self) -> u64 {
+        let generation = self.pause();
+        self.pause_reason = Some(PauseReason::Schedule);
+        generation
+    }
+
+    pub fn is_paused_by_schedule(&self) -> bool {
+        self.is_paused() && self.pause_reason == Some(PauseReason::Schedule)
     }
 
     pub fn complete(&mut self) {
+        self.session = None;
         self.status = TaskStatus::Completed;
         self.progress = 100.0;
         self.finished_at = Some(chrono::Utc::now().timestamp_millis() as usize);
+        self.refresh_speed();
     }
 
     pub fn fail(&mut self, error_message: String) {
         self.status = TaskStatus::Failed;
         self.error_message = Some(error_message);
+        self.error_kind = Some(ErrorKind::Unknown);
         self.finished_at = Some(chrono::Utc::now().timestamp_millis() as usize);
+        self.refresh_speed();
     }
 
     pub fn cancel(&mut self) {
+        self.session = None;
         self.total_transmit_bytes = 0;
         self.progress = 0.0;
         self.status = TaskStatus::Cancelled;
         self.finished_at = Some(chrono::Utc::now().timestamp_millis() as usize);
+        self.refresh_speed();
     }
 
     pub fn retry(&mut self) {
@@ -111,18 +521,119 @@ impl UploadTask {
         self.started_at = None;
         self.finished_at = None;
         self.retry_count += 1;
+        self.auto_retries = 0;
+        self.next_retry_at = None;
+        self.error_kind = None;
+        self.refresh_speed();
+    }
+
+    /// 标记失败并记录原因分类
+    pub fn fail_with(&mut self, kind: ErrorKind, error_message: String) {
+        self.fail(error_message);
+        self.error_kind = Some(kind);
+    }
+
+    /// 记录本次失败的原因
+    pub fn record_attempt(&mut self, kind: ErrorKind, error: String) {
+        self.attempts.push(UploadAttempt {
+            started_at: self.started_at,
+            failed_at: chrono::Utc::now().timestamp_millis() as usize,
+            line: self.session.as_ref().map(|session| session.line.clone()),
+            kind,
+            error,
+        });
+    }
+
+    /// 等待 delay 后自动重试，保留错误信息供前端显示
+    pub fn schedule_retry(&mut self, delay: Duration, error_message: String) {
+        let now = chrono::Utc::now().timestamp_millis() as usize;
+        self.status = TaskStatus::Pending;
+        self.error_message = Some(error_message);
+        self.started_at = None;
+        self.finished_at = None;
+        self.retry_count += 1;
+        self.auto_retries += 1;
+        self.next_retry_at = Some(now + delay.as_millis() as usize);
+        self.refresh_speed();
+    }
+
+    /// 是否在等待自动重试的时间
+    pub fn is_backing_off(&self) -> bool {
+        let now = chrono::Utc::now().timestamp_millis() as usize;
+        self.next_retry_at.is_some_and(|at| at > now)
     }
 
     pub fn retry_count(&self) -> u32 {
         self.retry_count
     }
 
-    pub fn update_progress(&mut self, progress: f64) {
-        self.progress = progress.clamp(0.0, 100.0);
+    pub fn set_session(&mut self, session: UploadSession) {
+        self.session = Some(session);
+        self.progress = self.session_progress();
+    }
+
+    /// 丢弃失效的上传会话，下次上传从头开始
+    pub fn reset_session(&mut self) {
+        self.session = None;
+        self.progress = 0.0;
+    }
+
+    /// 记录已上传完成的分片
+    pub fn record_chunk(&mut self, part: UploadedPart) {
+        if let Some(session) = self.session.as_mut() {
+            session.record_part(part);
+        }
+        self.progress = self.session_progress();
+    }
+
+    fn session_progress(&self) -> f64 {
+        match &self.session {
+            Some(session) if self.total_size > 0 => {
+                (session.uploaded_bytes() as f64 / self.total_size as f64 * 100.0).clamp(0.0, 100.0)
+            }
+            _ => 0.0,
+        }
     }
 
     pub fn update_total_transmit_bytes(&mut self, total_transmit_bytes: u64) {
         self.total_transmit_bytes += total_transmit_bytes;
+        self.speed_meter.record(total_transmit_bytes);
+        self.refresh_speed();
+    }
+
+    /// 已确认上传完成的字节数
+    pub fn uploaded_bytes(&self) -> u64 {
+        if self.is_completed() {
+            self.total_size
+        } else {
+            (self.total_size as f64 * self.progress / 100.0) as u64
+        }
+    }
+
+    /// 重新计算速率和剩余时间，未运行的任务速率为 0
+    pub fn refresh_speed(&mut self) {
+        if !self.is_running() {
+            self.speed_bytes_per_sec = 0.0;
+            self.eta_secs = None;
+            return;
+        }
+
+        self.speed_bytes_per_sec = self.speed_meter.speed();
+
+        let now = chrono::Utc::now().timestamp_millis() as usize;
+        let elapsed_secs = self
+            .started_at
+            .map(|started| now.saturating_sub(started) as f64 / 1000.0)
+            .unwrap_or(0.0);
+        self.average_speed = if elapsed_secs > 0.0 {
+            self.total_transmit_bytes as f64 / elapsed_secs
+        } else {
+            0.0
+        };
+
+        let remaining = self.total_size.saturating_sub(self.uploaded_bytes());
+        self.eta_secs = (self.speed_bytes_per_sec > 0.0)
+            .then(|| (remaining as f64 / self.speed_bytes_per_sec).ceil() as u64);
     }
 
     pub fn is_waiting(&self) -> bool {
@@ -141,10 +652,6 @@ impl UploadTask {
         matches!(self.status, TaskStatus::Paused)
     }
 
-    pub fn is_cancelled(&self) -> bool {
-        matches!(self.status, TaskStatus::Cancelled)
-    }
-
     pub fn is_completed(&self) -> bool {
         matches!(self.status, TaskStatus::Completed)
     }
@@ -161,3 +668,225 @@ impl UploadTask {
         &self.clients.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::OpenOptions, io::Write, time::SystemTime};
+
+    use super::*;
+
+    /// 临时文件，测试结束后删除
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, len: usize) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "biliup-app-{}-{}.bin",
+                std::process::id(),
+                name
+            ));
+            let content: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            std::fs::write(&path, content).unwrap();
+            Self(path)
+        }
+
+        fn write_at(&self, offset: u64, bytes: &[u8]) {
+            let mut file = OpenOptions::new().write(true).open(&self.0).unwrap();
+            let mtime = file.metadata().unwrap().modified().unwrap();
+            file.seek(SeekFrom::Start(offset)).unwrap();
+            file.write_all(bytes).unwrap();
+            // 保留修改时间，只让内容发生变化
+            file.set_modified(mtime).unwrap();
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    const LEN: usize = 4 * SAMPLE_SIZE as usize;
+
+    fn task(id: &str, total_size: u64) -> UploadTask {
+        let mut task = UploadTask::new(
+            &User::new(1, "user".to_string(), String::new()),
+            "template",
+            &VideoInfo::from_path(id.to_string(), Path::new("/videos/part.mp4")),
+            None,
+            detached_config(),
+            detached_clients().0,
+        );
+        task.total_size = total_size;
+        task
+    }
+
+    fn secs_ago(now: Instant, secs: u64) -> Instant {
+        now.checked_sub(Duration::from_secs(secs)).unwrap()
+    }
+
+    #[test]
+    fn speed_counts_only_the_window() {
+        let now = Instant::now();
+        let mut meter = SpeedMeter::default();
+        meter.record_at(secs_ago(now, 7), 10_000);
+        meter.record_at(secs_ago(now, 4), 2_000);
+        meter.record_at(now, 2_000);
+        // 7 秒前的样本已滑出窗口，剩余样本跨 4 秒
+        assert_eq!(meter.speed_at(now), 1_000.0);
+        assert_eq!(meter.samples.len(), 2);
+
+        assert_eq!(meter.speed_at(now + Duration::from_secs(10)), 0.0);
+        assert!(meter.samples.is_empty());
+    }
+
+    #[test]
+    fn speed_window_starts_at_one_second() {
+        let now = Instant::now();
+        let mut meter = SpeedMeter::default();
+        meter.record_at(now, 500);
+        assert_eq!(meter.speed_at(now), 500.0);
+        meter.reset();
+        assert_eq!(meter.speed_at(now), 0.0);
+    }
+
+    #[test]
+    fn eta_uses_remaining_bytes() {
+        let mut task = task("eta", 10_000);
+        task.start();
+        task.progress = 50.0;
+        task.update_total_transmit_bytes(1_000);
+        assert_eq!(task.speed_bytes_per_sec, 1_000.0);
+        assert_eq!(task.eta_secs, Some(5));
+
+        task.pause();
+        assert_eq!(task.speed_bytes_per_sec, 0.0);
+        assert_eq!(task.eta_secs, None);
+    }
+
+    fn session(total_size: u64, chunk_size: u64) -> UploadSession {
+        UploadSession {
+            line: "bda2".to_string(),
+            url: String::new(),
+            auth: String::new(),
+            upload_id: "upload".to_string(),
+            upos_uri: "upos://ugcfx2lf/n1.mp4".to_string(),
+            biz_id: 1,
+            chunk_size,
+            total_size,
+            created_at: 0,
+            parts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn pause_generation_tracks_each_pause() {
+        let mut task = task("pause", 100);
+        task.start();
+        let first = task.pause();
+        assert!(task.is_paused_since(first));
+        assert_eq!(task.pause_reason, Some(PauseReason::User));
+
+        // 期间继续过，旧的暂停计时器不再生效
+        task.pending();
+        assert!(!task.is_paused_since(first));
+        assert_eq!(task.pause_reason, None);
+
+        let second = task.pause_for_schedule();
+        assert_ne!(first, second);
+        assert!(!task.is_paused_since(first));
+        assert!(task.is_paused_since(second));
+        assert!(task.is_paused_by_schedule());
+    }
+
+    #[test]
+    fn resume_keeps_uploaded_chunks() {
+        let mut task = task("resume", 100);
+        task.start();
+        task.set_session(session(100, 25));
+        task.record_chunk(UploadedPart {
+            index: 0,
+            etag: "etag-0".to_string(),
+        });
+        task.update_total_transmit_bytes(40);
+        assert_eq!(task.progress, 25.0);
+
+        task.pause();
+        task.pending();
+        task.start();
+        // 重新开始只清零本次发送的字节数，已确认的分片和进度保留
+        assert_eq!(task.total_transmit_bytes, 0);
+        assert_eq!(task.progress, 25.0);
+        assert_eq!(task.session.as_ref().unwrap().missing_chunks(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn fingerprint_unchanged_file() {
+        let file = TempFile::new("same", LEN);
+        let before = SourceFingerprint::read(&file.0).unwrap();
+        let after = SourceFingerprint::read(&file.0).unwrap();
+        assert_eq!(before, after);
+        assert_eq!(before.diff(&after), None);
+        assert_eq!(before.size, LEN as u64);
+    }
+
+    #[test]
+    fn fingerprint_detects_size_change() {
+        let file = TempFile::new("size", LEN);
+        let before = SourceFingerprint::read(&file.0).unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(&file.0)
+            .unwrap()
+            .write_all(b"more")
+            .unwrap();
+        let after = SourceFingerprint::read(&file.0).unwrap();
+        assert_eq!(
+            before.diff(&after),
+            Some(format!("大小 {} -> {}", LEN, LEN + 4))
+        );
+    }
+
+    #[test]
+    fn fingerprint_detects_mtime_change() {
+        let file = TempFile::new("mtime", LEN);
+        let before = SourceFingerprint::read(&file.0).unwrap();
+        File::options()
+            .write(true)
+            .open(&file.0)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(3600))
+            .unwrap();
+        let after = SourceFingerprint::read(&file.0).unwrap();
+        assert_eq!(before.partial_hash, after.partial_hash);
+        assert_eq!(before.diff(&after).as_deref(), Some("修改时间变化"));
+    }
+
+    #[test]
+    fn fingerprint_detects_sampled_block_change() {
+        let file = TempFile::new("content", LEN);
+        let before = SourceFingerprint::read(&file.0).unwrap();
+
+        // 头尾之间未采样的内容变化不影响指纹
+        file.write_at(SAMPLE_SIZE + 1, &[0xff; 16]);
+        let unsampled = SourceFingerprint::read(&file.0).unwrap();
+        assert_eq!(before.diff(&unsampled), None);
+
+        for offset in [0, LEN as u64 / 2, LEN as u64 - 1] {
+            file.write_at(offset, &[0xff]);
+            let after = SourceFingerprint::read(&file.0).unwrap();
+            assert_eq!(before.mtime, after.mtime);
+            assert_eq!(before.diff(&after).as_deref(), Some("文件内容变化"));
+            file.write_at(offset, &[(offset % 251) as u8]);
+        }
+    }
+
+    #[test]
+    fn fingerprint_small_file() {
+        let file = TempFile::new("small", 10);
+        let before = SourceFingerprint::read(&file.0).unwrap();
+        file.write_at(5, &[0xff]);
+        let after = SourceFingerprint::read(&file.0).unwrap();
+        assert_eq!(before.diff(&after).as_deref(), Some("文件内容变化"));
+    }
+}
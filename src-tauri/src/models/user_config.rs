@@ -1,7 +1,13 @@
 use anyhow::Result;
 use biliup::credential::LoginInfo;
+use chrono::{DateTime, Datelike, Local, NaiveTime};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use tracing::{debug, info};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,6 +49,50 @@ pub struct VideoInfo {
     pub group_key: String,
     #[serde(default)]
     pub group_role: String,
+    /// 创建任务时 ffprobe 读取的媒体信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probe: Option<MediaProbe>,
+}
+
+impl VideoInfo {
+    /// 用本地文件创建分P，标题取文件名
+    pub fn from_path(id: String, path: &Path) -> Self {
+        let title = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self {
+            id,
+            cid: 0,
+            title,
+            filename: String::new(),
+            desc: String::new(),
+            path: path.to_string_lossy().to_string(),
+            finished_at: 0,
+            encoding_status: 0,
+            status_desc: String::new(),
+            group_key: String::new(),
+            group_role: String::new(),
+            probe: None,
+        }
+    }
+}
+
+/// ffprobe 读取的媒体信息
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MediaProbe {
+    pub format: String,
+    /// 时长（秒）
+    pub duration: f64,
+    pub width: u32,
+    pub height: u32,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    /// 总码率，单位比特每秒
+    pub bit_rate: u64,
+    /// 检查发现但未拒绝的问题
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,6 +109,9 @@ pub struct TemplateConfig {
     pub title: String,
     #[serde(default)]
     pub title_prefix: String,
+    /// 分P标题模板，为空时保留各分P原有标题
+    #[serde(default)]
+    pub part_title: String,
     #[serde(default)]
     pub desc: String,
     #[serde(default)]
@@ -122,10 +175,238 @@ pub struct UserConfig {
     pub watermark: u8,
     #[serde(default)]
     pub auto_edit: u8,
+    /// 该账号的上传带宽上限，单位字节每秒，0 表示不限制
+    #[serde(default)]
+    pub bandwidth_limit: u64,
+    /// 该账号同时上传的最大任务数，0 表示只受全局并发数限制
+    #[serde(default)]
+    pub max_concurrent: u32,
     #[serde(default)]
     pub templates: HashMap<String, TemplateConfig>, // ƥconfig.jsonе"templates"ֶ
 }
 
+/// 按时段调整并发数和带宽的规则
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduleRule {
+    /// 生效的星期，1-7 表示周一到周日，为空时每天生效
+    #[serde(default)]
+    pub weekdays: Vec<u8>,
+    /// 开始时间，格式为 HH:MM
+    pub start: String,
+    /// 结束时间，早于开始时间时表示跨越午夜
+    pub end: String,
+    /// 时段内的最大并发数，0 表示沿用全局设置
+    #[serde(default)]
+    pub max_curr: u32,
+    /// 时段内的全局带宽上限，单位字节每秒，0 表示不限制
+    #[serde(default)]
+    pub bandwidth_limit: u64,
+}
+
+impl ScheduleRule {
+    fn on_weekday(&self, weekday: u8) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&weekday)
+    }
+
+    pub fn is_active(&self, now: &DateTime<Local>) -> bool {
+        let (Ok(start), Ok(end)) = (
+            NaiveTime::parse_from_str(&self.start, "%H:%M"),
+            NaiveTime::parse_from_str(&self.end, "%H:%M"),
+        ) else {
+            return false;
+        };
+
+        let time = now.time();
+        let weekday = now.weekday().number_from_monday() as u8;
+        if start == end {
+            self.on_weekday(weekday)
+        } else if start < end {
+            start <= time && time < end && self.on_weekday(weekday)
+        } else if time >= start {
+            self.on_weekday(weekday)
+        } else if time < end {
+            // 跨越午夜的时段按开始那天的星期计算
+            self.on_weekday(if weekday == 1 { 7 } else { weekday - 1 })
+        } else {
+            false
+        }
+    }
+}
+
+/// 供录制和后处理脚本使用的本地 HTTP 接口，只监听 127.0.0.1
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// 请求需携带的令牌，开启时为空会自动生成
+    pub token: String,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 19159,
+            token: String::new(),
+        }
+    }
+}
+
+impl ApiConfig {
+    /// 开启接口但没有令牌时生成一个，返回是否生成了新令牌
+    pub fn ensure_token(&mut self) -> bool {
+        if !self.enabled || !self.token.is_empty() {
+            return false;
+        }
+        self.token = (0..32)
+            .map(|_| fastrand::alphanumeric())
+            .collect::<String>();
+        true
+    }
+}
+
+/// 上传失败、投稿成功等事件的通知地址
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub name: String,
+    pub url: String,
+    pub enabled: bool,
+    /// 订阅的事件，为空时订阅全部：task.status_changed、task.completed、task.failed、
+    /// submit.succeeded、submit.failed
+    pub events: Vec<String>,
+    /// 请求体模板，{{title}} 等变量按 JSON 字符串转义后替换，为空时发送完整的事件 JSON
+    pub payload_template: String,
+    /// 签名密钥，为空时不签名
+    pub secret: String,
+    /// 携带 HMAC-SHA256 签名的请求头
+    pub signature_header: String,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            url: String::new(),
+            enabled: true,
+            events: Vec::new(),
+            payload_template: String::new(),
+            secret: String::new(),
+            signature_header: "X-Biliup-Signature".to_string(),
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn accepts(&self, event: &str) -> bool {
+        self.enabled
+            && !self.url.is_empty()
+            && (self.events.is_empty() || self.events.iter().any(|e| e == event))
+    }
+}
+
+/// 标题翻译使用的接口
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslationProvider {
+    #[default]
+    OpenAi, // OpenAI 兼容的 chat/completions
+    DeepL,
+    Ollama,
+}
+
+/// 后台监控的文件夹，新文件写入完成后加入上传队列
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct WatchConfig {
+    pub path: String,
+    pub enabled: bool,
+    /// 文件名通配符（如 `*.flv`）或逗号分隔的扩展名，为空时匹配常见视频格式
+    pub filter: String,
+    pub recursive: bool,
+    pub uid: u64,
+    pub template: String,
+    /// 文件大小和修改时间保持不变的秒数，达到后才加入队列
+    pub stable_secs: u64,
+    /// 加入队列后立即开始上传
+    pub auto_start: bool,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            enabled: true,
+            filter: String::new(),
+            recursive: false,
+            uid: 0,
+            template: String::new(),
+            stable_secs: 30,
+            auto_start: true,
+        }
+    }
+}
+
+/// 创建上传任务时用 ffprobe 检查视频文件
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ProbeConfig {
+    pub enabled: bool,
+    /// 发现问题时拒绝创建任务，否则仅提示。命令行、HTTP 接口和文件夹监控同样会被拒绝，
+    /// 被拒绝的监控文件不会自动重试，需修改文件或手动添加
+    pub reject: bool,
+    /// 文件大小上限，单位字节，0 表示不检查
+    pub max_size: u64,
+    /// 时长上限（秒），0 表示不检查
+    pub max_duration_secs: u64,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reject: true,
+            max_size: 16 << 30,
+            max_duration_secs: 10 * 60 * 60,
+        }
+    }
+}
+
+/// 上传失败后自动重试的策略
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// 每个任务最多尝试的次数，包括第一次上传，1 表示不自动重试
+    pub max_attempts: u32,
+    /// 第一次重试前等待的秒数，之后每次翻倍
+    pub base_delay_secs: u64,
+    /// 单次等待的上限（秒）
+    pub max_delay_secs: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay_secs: 10,
+            max_delay_secs: 600,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 第 n 次自动重试前的等待时间，在指数退避的基础上随机取后一半，避免多个任务同时重试
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exp = self
+            .base_delay_secs
+            .saturating_mul(1u64 << retry.saturating_sub(1).min(20))
+            .min(self.max_delay_secs.max(self.base_delay_secs));
+        let millis = exp.saturating_mul(1000);
+        Duration::from_millis(millis / 2 + fastrand::u64(0..=millis / 2))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigRoot {
     #[serde(default)]
@@ -146,6 +427,27 @@ pub struct ConfigRoot {
     pub translation_prompt: String,
     #[serde(default)]
     pub translation_auto: bool,
+    /// 创建任务时翻译分P标题，独立于编辑器中的稿件标题自动翻译
+    #[serde(default)]
+    pub translation_auto_parts: bool,
+    #[serde(default)]
+    pub translation_provider: TranslationProvider,
+    /// 所有上传共享的带宽上限，单位字节每秒，0 表示不限制
+    #[serde(default)]
+    pub bandwidth_limit: u64,
+    /// 按时段生效的限速规则，多条规则同时生效时取第一条
+    #[serde(default)]
+    pub schedule: Vec<ScheduleRule>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    pub probe: ProbeConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub watches: Vec<WatchConfig>,
     #[serde(default)]
     pub config: HashMap<u64, UserConfig>,
 }
@@ -196,6 +498,8 @@ impl ConfigRoot {
             limit: 0,
             watermark: 0,
             auto_edit: 0,
+            bandwidth_limit: 0,
+            max_concurrent: 0,
             templates: HashMap::new(),
         };
         self.config.insert(uid, user_config);
@@ -226,6 +530,7 @@ impl ConfigRoot {
         limit: u32,
         watermark: u8,
         auto_edit: u8,
+        max_concurrent: Option<u32>,
     ) -> Result<&Self> {
         if let Some(user_config) = self.config.get_mut(&uid) {
             info!(
@@ -237,6 +542,9 @@ impl ConfigRoot {
             user_config.limit = limit;
             user_config.watermark = watermark;
             user_config.auto_edit = auto_edit;
+            if let Some(max_concurrent) = max_concurrent {
+                user_config.max_concurrent = max_concurrent;
+            }
             Ok(self)
         } else {
             Err(anyhow::anyhow!("ûò"))
@@ -276,6 +584,25 @@ impl ConfigRoot {
         self
     }
 
+    /// 设置带宽上限，uid 为空时设置全局上限
+    pub fn set_bandwidth_limit(&mut self, uid: Option<u64>, bytes_per_sec: u64) -> Result<&Self> {
+        match uid {
+            None => self.bandwidth_limit = bytes_per_sec,
+            Some(uid) => {
+                self.config
+                    .get_mut(&uid)
+                    .ok_or_else(|| anyhow::anyhow!("用户配置不存在: {uid}"))?
+                    .bandwidth_limit = bytes_per_sec
+            }
+        }
+        Ok(self)
+    }
+
+    /// 当前生效的时段规则
+    pub fn active_schedule(&self, now: &DateTime<Local>) -> Option<&ScheduleRule> {
+        self.schedule.iter().find(|rule| rule.is_active(now))
+    }
+
     pub fn add_user_template(
         &mut self,
         uid: u64,
@@ -318,6 +645,15 @@ impl ConfigRoot {
             translation_model: String::new(),
             translation_prompt: default_translation_prompt(),
             translation_auto: false,
+            translation_auto_parts: false,
+            translation_provider: TranslationProvider::default(),
+            bandwidth_limit: 0,
+            schedule: Vec::new(),
+            retry: RetryPolicy::default(),
+            probe: ProbeConfig::default(),
+            api: ApiConfig::default(),
+            webhooks: Vec::new(),
+            watches: Vec::new(),
             config: HashMap::new(),
         }
     }
@@ -364,6 +700,7 @@ impl ConfigRoot {
             compare_field!(cover, old, new);
             compare_field!(title, old, new);
             compare_field!(title_prefix, old, new);
+            compare_field!(part_title, old, new);
             compare_field!(desc, old, new);
             compare_field!(desc_v2, old, new);
             compare_field!(dynamic, old, new);
@@ -398,6 +735,7 @@ impl Default for TemplateConfig {
             cover: String::new(),
             title: String::new(),
             title_prefix: String::new(),
+            part_title: String::new(),
             desc: String::new(),
             desc_v2: None,
             dynamic: String::new(),
@@ -425,3 +763,101 @@ impl Default for TemplateConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn assert_delay_between(policy: &RetryPolicy, retry: u32, min_secs: u64, max_secs: u64) {
+        for _ in 0..100 {
+            let delay = policy.delay(retry);
+            assert!(
+                delay >= Duration::from_secs(min_secs) && delay <= Duration::from_secs(max_secs),
+                "第 {retry} 次重试的等待时间 {delay:?} 不在 {min_secs}-{max_secs} 秒之间"
+            );
+        }
+    }
+
+    #[test]
+    fn retry_delay_doubles_within_bounds() {
+        let policy = RetryPolicy::default();
+        assert_delay_between(&policy, 0, 5, 10);
+        assert_delay_between(&policy, 1, 5, 10);
+        assert_delay_between(&policy, 2, 10, 20);
+        assert_delay_between(&policy, 3, 20, 40);
+        assert_delay_between(&policy, 10, 300, 600);
+        assert_delay_between(&policy, u32::MAX, 300, 600);
+    }
+
+    #[test]
+    fn retry_delay_never_below_base() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay_secs: 30,
+            max_delay_secs: 10,
+        };
+        assert_delay_between(&policy, 4, 15, 30);
+
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay_secs: 0,
+            max_delay_secs: 600,
+        };
+        assert_eq!(policy.delay(3), Duration::ZERO);
+    }
+
+    fn rule(weekdays: &[u8], start: &str, end: &str) -> ScheduleRule {
+        ScheduleRule {
+            weekdays: weekdays.to_vec(),
+            start: start.to_string(),
+            end: end.to_string(),
+            max_curr: 0,
+            bandwidth_limit: 0,
+        }
+    }
+
+    /// 2024-01-01 是周一
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2024, 1, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn schedule_within_day() {
+        let rule = rule(&[], "08:00", "12:00");
+        assert!(rule.is_active(&at(1, 8, 0)));
+        assert!(rule.is_active(&at(1, 11, 59)));
+        assert!(!rule.is_active(&at(1, 12, 0)));
+        assert!(!rule.is_active(&at(1, 7, 59)));
+    }
+
+    #[test]
+    fn schedule_across_midnight_uses_start_weekday() {
+        let rule = rule(&[1], "23:00", "02:00");
+        assert!(rule.is_active(&at(1, 23, 30)));
+        // 周二凌晨属于周一开始的时段
+        assert!(rule.is_active(&at(2, 1, 0)));
+        assert!(!rule.is_active(&at(2, 2, 0)));
+        assert!(!rule.is_active(&at(2, 23, 30)));
+        // 周一凌晨属于周日开始的时段
+        assert!(!rule.is_active(&at(1, 1, 0)));
+    }
+
+    #[test]
+    fn schedule_across_midnight_from_sunday() {
+        let rule = rule(&[7], "22:00", "06:00");
+        assert!(rule.is_active(&at(7, 22, 0)));
+        assert!(rule.is_active(&at(1, 5, 59)));
+        assert!(rule.is_active(&at(8, 1, 0)));
+        assert!(!rule.is_active(&at(2, 1, 0)));
+    }
+
+    #[test]
+    fn schedule_full_day_and_invalid_time() {
+        assert!(rule(&[1], "00:00", "00:00").is_active(&at(1, 15, 0)));
+        assert!(!rule(&[1], "00:00", "00:00").is_active(&at(2, 15, 0)));
+        assert!(!rule(&[], "25:00", "06:00").is_active(&at(1, 3, 0)));
+    }
+}
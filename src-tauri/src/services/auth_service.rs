@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{models::User, utils::crypto::encode_base64};
 use anyhow::Result;
@@ -7,8 +7,8 @@ use biliup::{
     credential::{Credential, LoginInfo},
 };
 use qrcode::QrCode;
-use serde_json::Value;
-use tracing::{debug, info};
+use serde_json::{Value, json};
+use tracing::{debug, info, warn};
 
 pub struct AuthService {
     credential: Option<Credential>,
@@ -126,12 +126,6 @@ impl AuthService {
         }
     }
 
-    /// Cookie 登录
-    pub async fn _cookie_login(&self, _cookie: &str) -> Result<(BiliBili, User)> {
-        // TODO: 实现 Cookie 登录验证
-        todo!("实现 Cookie 登录")
-    }
-
     pub fn destroy(&mut self) {
         self.credential = None;
         self.qr_key = None;
@@ -139,30 +133,208 @@ impl AuthService {
     }
 
     async fn login_done(&self, login_info: &LoginInfo) -> Result<(BiliBili, User)> {
-        info!("登录完成，开始获取用户信息");
-        let bilibili =
-            biliup::credential::bilibili_from_info(login_info.clone(), self.proxy.as_deref())?;
-
-        let myinfo = bilibili.my_info().await?;
-        let username = myinfo["data"]["name"].as_str().unwrap().to_owned();
-        let uid = myinfo["data"]["mid"].as_u64().unwrap_or(0);
-        let avatar_url = myinfo["data"]["face"].as_str().unwrap_or("").to_string();
-        debug!("头像 URL: {}", avatar_url);
-
-        let avatar = bilibili
-            .client
-            .get(&avatar_url)
-            .send()
-            .await
-            .map_err(|e| anyhow::anyhow!("获取用户头像失败: {}", e))?
-            .bytes()
-            .await?;
-        let avatar = encode_base64(&avatar);
-        debug!("头像 URL: {}", avatar_url);
-        Ok((bilibili, User::new(uid, username, avatar)))
+        fetch_user(login_info, self.proxy.as_deref()).await
     }
 }
 
+/// Cookie 登录，支持 Cookie 请求头、浏览器导出的 JSON 和 Netscape cookies.txt
+///
+/// 不依赖 AuthService 的状态，调用方不需要持有应用锁
+pub async fn cookie_login(input: &str, proxy: Option<&str>) -> Result<(BiliBili, User)> {
+    info!("开始 Cookie 登录");
+    let login_info = login_info_from_cookies(&parse_cookies(input)?)?;
+    let (bilibili, user) = fetch_user(&login_info, proxy).await?;
+    // 只有 Cookie 时没有 access_token
+    warn!(
+        "用户 {} 通过 Cookie 登录，没有 access_token，客户端接口可能不可用",
+        user.uid
+    );
+    Ok((bilibili, user))
+}
+
+/// 用登录信息创建客户端并获取用户信息，登录信息失效时返回错误
+async fn fetch_user(login_info: &LoginInfo, proxy: Option<&str>) -> Result<(BiliBili, User)> {
+    info!("登录完成，开始获取用户信息");
+    let bilibili = biliup::credential::bilibili_from_info(login_info.clone(), proxy)?;
+
+    let myinfo = bilibili.my_info().await?;
+    // 登录信息失效时 data 为空
+    let username = myinfo["data"]["name"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("登录信息无效: {}", myinfo["message"]))?
+        .to_owned();
+    let uid = myinfo["data"]["mid"].as_u64().unwrap_or(0);
+    let avatar_url = myinfo["data"]["face"].as_str().unwrap_or("").to_string();
+    debug!("头像 URL: {}", avatar_url);
+
+    let avatar = bilibili
+        .client
+        .get(&avatar_url)
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("获取用户头像失败: {}", e))?
+        .bytes()
+        .await?;
+    let avatar = encode_base64(&avatar);
+    debug!("头像 URL: {}", avatar_url);
+    Ok((bilibili, User::new(uid, username, avatar)))
+}
+
+/// 从 Cookie 中读取的一项
+#[derive(Debug, Clone, PartialEq)]
+struct CookieEntry {
+    name: String,
+    value: String,
+    /// 过期时间戳（秒），0 表示会话 Cookie
+    expires: i64,
+}
+
+/// 解析各种格式的 Cookie，内容是已存在的文件路径时读取文件
+fn parse_cookies(input: &str) -> Result<Vec<CookieEntry>> {
+    let input = input.trim();
+    let content;
+    let input = if !input.contains('\n') && Path::new(input).is_file() {
+        content = std::fs::read_to_string(input)?;
+        content.trim()
+    } else {
+        input
+    };
+    if input.is_empty() {
+        anyhow::bail!("Cookie 为空");
+    }
+
+    let cookies = if input.starts_with('[') || input.starts_with('{') {
+        parse_json_cookies(input)?
+    } else if input
+        .lines()
+        .any(|line| line.starts_with("# Netscape") || line.split('\t').count() >= 7)
+    {
+        parse_netscape_cookies(input)
+    } else {
+        parse_cookie_header(input)
+    };
+
+    for required in ["SESSDATA", "bili_jct", "DedeUserID"] {
+        if !cookies.iter().any(|cookie| cookie.name == required) {
+            anyhow::bail!("Cookie 缺少 {required}");
+        }
+    }
+    Ok(cookies)
+}
+
+/// `Cookie: a=1; b=2`，前缀可省略
+fn parse_cookie_header(input: &str) -> Vec<CookieEntry> {
+    let header = input
+        .strip_prefix("Cookie:")
+        .or_else(|| input.strip_prefix("cookie:"))
+        .unwrap_or(input);
+    header
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .map(|(name, value)| CookieEntry {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+            expires: 0,
+        })
+        .collect()
+}
+
+/// 浏览器扩展导出的数组，或 biliup 的 cookies.json
+fn parse_json_cookies(input: &str) -> Result<Vec<CookieEntry>> {
+    let value: Value = serde_json::from_str(input)?;
+    let items = match &value {
+        Value::Array(items) => items,
+        _ => value["cookie_info"]["cookies"]
+            .as_array()
+            .or_else(|| value["cookies"].as_array())
+            .ok_or_else(|| anyhow::anyhow!("无法识别的 Cookie JSON"))?,
+    };
+
+    Ok(items
+        .iter()
+        .filter(|item| {
+            item["domain"]
+                .as_str()
+                .is_none_or(|domain| domain.contains("bilibili"))
+        })
+        .filter_map(|item| {
+            let name = item["name"].as_str()?;
+            let value = item["value"].as_str()?;
+            let expires = item["expirationDate"]
+                .as_f64()
+                .or_else(|| item["expires"].as_f64())
+                .unwrap_or(0.0);
+            Some(CookieEntry {
+                name: name.to_string(),
+                value: value.to_string(),
+                expires: expires as i64,
+            })
+        })
+        .collect())
+}
+
+/// 每行为 域名、子域、路径、secure、过期时间、名称、值，以制表符分隔
+fn parse_netscape_cookies(input: &str) -> Vec<CookieEntry> {
+    input
+        .lines()
+        .map(|line| line.strip_prefix("#HttpOnly_").unwrap_or(line))
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 7 || !fields[0].contains("bilibili") {
+                return None;
+            }
+            Some(CookieEntry {
+                name: fields[5].to_string(),
+                value: fields[6].trim_end().to_string(),
+                expires: fields[4].parse().unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// 与二维码登录保存的格式相同，没有 access_token
+fn login_info_from_cookies(cookies: &[CookieEntry]) -> Result<LoginInfo> {
+    let mid = cookies
+        .iter()
+        .find(|cookie| cookie.name == "DedeUserID")
+        .and_then(|cookie| cookie.value.parse::<u64>().ok())
+        .ok_or_else(|| anyhow::anyhow!("DedeUserID 无效"))?;
+    let cookies: Vec<Value> = cookies
+        .iter()
+        .map(|cookie| {
+            json!({
+                "name": cookie.name,
+                "value": cookie.value,
+                "http_only": 0,
+                "expires": cookie.expires,
+                "secure": 0,
+            })
+        })
+        .collect();
+
+    Ok(serde_json::from_value(json!({
+        "cookie_info": {
+            "cookies": cookies,
+            "domains": [
+                ".bilibili.com",
+                ".biligame.com",
+                ".bigfun.cn",
+                ".bigfunapp.cn",
+                ".dreamcast.hk",
+            ],
+        },
+        "sso": [],
+        "token_info": {
+            "access_token": "",
+            "expires_in": 0,
+            "mid": mid,
+            "refresh_token": "",
+        },
+        "platform": null,
+    }))?)
+}
+
 pub async fn validate_cookie_in_old_config(cookie: &PathBuf) -> Result<(BiliBili, User)> {
     let bilibili = biliup::credential::login_by_cookies(cookie, None).await?;
 
@@ -175,3 +347,72 @@ pub async fn validate_cookie_in_old_config(cookie: &PathBuf) -> Result<(BiliBili
     info!("验证用户: {}", user_name);
     Ok((bilibili, User::new(uid, user_name, avatar_url)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value_of<'a>(cookies: &'a [CookieEntry], name: &str) -> Option<&'a str> {
+        cookies
+            .iter()
+            .find(|cookie| cookie.name == name)
+            .map(|cookie| cookie.value.as_str())
+    }
+
+    #[test]
+    fn parses_cookie_header() {
+        let cookies =
+            parse_cookies("Cookie: SESSDATA=abc%2C1; bili_jct=csrf; DedeUserID=42").unwrap();
+        assert_eq!(value_of(&cookies, "SESSDATA"), Some("abc%2C1"));
+        assert_eq!(value_of(&cookies, "bili_jct"), Some("csrf"));
+        assert_eq!(value_of(&cookies, "DedeUserID"), Some("42"));
+        assert!(cookies.iter().all(|cookie| cookie.expires == 0));
+    }
+
+    #[test]
+    fn parses_exported_json_array() {
+        let input = r#"[
+            {"domain": ".bilibili.com", "name": "SESSDATA", "value": "abc", "expirationDate": 1700000000.5},
+            {"domain": ".bilibili.com", "name": "bili_jct", "value": "csrf"},
+            {"domain": ".bilibili.com", "name": "DedeUserID", "value": "42"},
+            {"domain": ".example.com", "name": "SESSDATA", "value": "other"}
+        ]"#;
+        let cookies = parse_cookies(input).unwrap();
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(value_of(&cookies, "SESSDATA"), Some("abc"));
+        assert_eq!(cookies[0].expires, 1700000000);
+    }
+
+    #[test]
+    fn parses_cookie_info_json() {
+        let input = r#"{"cookie_info": {"cookies": [
+            {"name": "SESSDATA", "value": "abc", "expires": 1700000000},
+            {"name": "bili_jct", "value": "csrf", "expires": 1700000000},
+            {"name": "DedeUserID", "value": "42", "expires": 1700000000}
+        ]}}"#;
+        let cookies = parse_cookies(input).unwrap();
+        assert_eq!(value_of(&cookies, "DedeUserID"), Some("42"));
+        assert!(cookies.iter().all(|cookie| cookie.expires == 1700000000));
+    }
+
+    #[test]
+    fn parses_netscape_with_http_only() {
+        let input = "# Netscape HTTP Cookie File\n\
+            #HttpOnly_.bilibili.com\tTRUE\t/\tTRUE\t1700000000\tSESSDATA\tabc\n\
+            .bilibili.com\tTRUE\t/\tFALSE\t1700000000\tbili_jct\tcsrf\n\
+            .bilibili.com\tTRUE\t/\tFALSE\t0\tDedeUserID\t42\n\
+            .example.com\tTRUE\t/\tFALSE\t0\tfoo\tbar\n";
+        let cookies = parse_cookies(input).unwrap();
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(value_of(&cookies, "SESSDATA"), Some("abc"));
+        assert_eq!(cookies[0].expires, 1700000000);
+        assert_eq!(value_of(&cookies, "foo"), None);
+    }
+
+    #[test]
+    fn rejects_missing_sessdata() {
+        let err = parse_cookies("bili_jct=csrf; DedeUserID=42").unwrap_err();
+        assert!(err.to_string().contains("SESSDATA"));
+        assert!(parse_cookies("   ").is_err());
+    }
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Result;
+use glob::{MatchOptions, Pattern};
+use notify::{EventKind, RecursiveMode, Watcher};
+use tauri::{AppHandle, Manager};
+use tokio::{
+    sync::{Mutex, mpsc},
+    task,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    AppData, commands,
+    models::{SourceFingerprint, VideoInfo, WatchConfig},
+    utils::get_watch_history_json_path,
+};
+
+/// 未设置过滤条件时匹配的扩展名
+const VIDEO_EXTENSIONS: [&str; 13] = [
+    "mp4", "flv", "avi", "wmv", "mov", "webm", "mpeg4", "ts", "mpg", "rm", "rmvb", "mkv", "m4v",
+];
+
+/// 等待写入完成的文件
+struct PendingFile {
+    watch: usize,
+    size: u64,
+    modified: Option<SystemTime>,
+    stable_since: Instant,
+}
+
+/// 按当前配置重新启动文件夹监控，没有开启的监控时只停止旧的
+pub async fn restart(app: AppHandle) -> Result<()> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let mut app_data = app_lock.lock().await;
+    if let Some(handle) = app_data.folder_watcher.take() {
+        handle.abort();
+        info!("文件夹监控已停止");
+    }
+
+    let watches: Vec<WatchConfig> = app_data
+        .config
+        .lock()
+        .await
+        .watches
+        .iter()
+        .filter(|watch| watch.enabled && !watch.path.is_empty())
+        .cloned()
+        .collect();
+    if !watches.is_empty() {
+        app_data.folder_watcher = Some(start(app.clone(), watches)?);
+    }
+    Ok(())
+}
+
+fn start(app: AppHandle, mut watches: Vec<WatchConfig>) -> Result<task::JoinHandle<()>> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("文件夹监控出错: {}", e),
+        })?;
+
+    for watch in &mut watches {
+        // 事件中的路径是绝对路径，比较前统一格式
+        if let Ok(path) = fs::canonicalize(&watch.path) {
+            watch.path = path.to_string_lossy().to_string();
+        }
+        let mode = if watch.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        match watcher.watch(Path::new(&watch.path), mode) {
+            Ok(()) => info!("开始监控文件夹: {} -> {}", watch.path, watch.template),
+            Err(e) => error!("监控文件夹失败: {} - {}", watch.path, e),
+        }
+    }
+
+    Ok(task::spawn(async move {
+        // 任务结束时 watcher 随之释放，停止监控
+        let _watcher = watcher;
+        let mut pending: HashMap<PathBuf, PendingFile> = HashMap::new();
+        // 已入队文件的源文件哈希，重启或文件改名后不会重复上传
+        let mut history = match load_history() {
+            Ok(history) => history,
+            Err(e) => {
+                warn!("读取文件夹监控记录失败: {}", e);
+                HashSet::new()
+            }
+        };
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                Some(path) = rx.recv() => {
+                    if pending.contains_key(&path) {
+                        continue;
+                    }
+                    if let Some(watch) = matching_watch(&watches, &path) {
+                        debug!("发现新文件，等待写入完成: {:?}", path);
+                        pending.insert(path, PendingFile {
+                            watch,
+                            size: 0,
+                            modified: None,
+                            stable_since: Instant::now(),
+                        });
+                    }
+                }
+                _ = interval.tick() => {
+                    for (path, watch) in stable_files(&watches, &mut pending) {
+                        enqueue(&app, &watches[watch], &path, &mut history).await;
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// 返回第一个匹配文件的监控
+fn matching_watch(watches: &[WatchConfig], path: &Path) -> Option<usize> {
+    let file_name = path.file_name()?.to_string_lossy();
+    watches.iter().position(|watch| {
+        let root = Path::new(&watch.path);
+        let in_scope = if watch.recursive {
+            path.starts_with(root)
+        } else {
+            path.parent() == Some(root)
+        };
+        in_scope && matches_filter(&watch.filter, &file_name)
+    })
+}
+
+fn matches_filter(filter: &str, file_name: &str) -> bool {
+    let filter = filter.trim();
+    if filter.contains(['*', '?', '[']) {
+        let options = MatchOptions {
+            case_sensitive: false,
+            ..MatchOptions::default()
+        };
+        return Pattern::new(filter).is_ok_and(|pattern| pattern.matches_with(file_name, options));
+    }
+
+    let Some((_, extension)) = file_name.rsplit_once('.') else {
+        return false;
+    };
+    if filter.is_empty() {
+        return VIDEO_EXTENSIONS
+            .iter()
+            .any(|ext| ext.eq_ignore_ascii_case(extension));
+    }
+    filter
+        .split(',')
+        .map(|ext| ext.trim().trim_start_matches('.'))
+        .any(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/// 取出大小和修改时间已稳定且没有进程在写入的文件
+fn stable_files(
+    watches: &[WatchConfig],
+    pending: &mut HashMap<PathBuf, PendingFile>,
+) -> Vec<(PathBuf, usize)> {
+    let now = Instant::now();
+    let mut ready = Vec::new();
+    pending.retain(|path, file| {
+        // 文件被删除或移走
+        let Ok(metadata) = fs::metadata(path) else {
+            return false;
+        };
+        if !metadata.is_file() {
+            return false;
+        }
+
+        let modified = metadata.modified().ok();
+        if metadata.len() != file.size || modified != file.modified {
+            file.size = metadata.len();
+            file.modified = modified;
+            file.stable_since = now;
+            return true;
+        }
+        let stable_secs = Duration::from_secs(watches[file.watch].stable_secs);
+        if file.size == 0 || now.duration_since(file.stable_since) < stable_secs {
+            return true;
+        }
+        if is_open_for_writing(path) {
+            return true;
+        }
+        ready.push((path.clone(), file.watch));
+        false
+    });
+    ready
+}
+
+async fn enqueue(app: &AppHandle, watch: &WatchConfig, path: &Path, history: &mut HashSet<String>) {
+    let hash = match SourceFingerprint::read_async(path).await {
+        Ok(source) => source.partial_hash,
+        Err(e) => {
+            warn!("读取监控文件失败: {:?} - {}", path, e);
+            return;
+        }
+    };
+    if history.contains(&hash) {
+        debug!("文件已加入过上传队列: {:?}", path);
+        return;
+    }
+    // 上传完成后任务中的路径会被清空，按创建任务时记录的源文件比较
+    match commands::get_upload_queue(app.clone()).await {
+        Ok(tasks)
+            if tasks.iter().any(|task| {
+                task.source
+                    .as_ref()
+                    .is_some_and(|source| source.partial_hash == hash)
+            }) =>
+        {
+            debug!("文件已在上传队列中: {:?}", path);
+            remember(history, hash);
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => warn!("读取上传队列失败: {}", e),
+    }
+
+    let task_id = format!(
+        "watch-{}-{}",
+        chrono::Utc::now().timestamp_millis(),
+        fastrand::u32(..)
+    );
+    let video = VideoInfo::from_path(task_id.clone(), path);
+    if let Err(e) =
+        commands::create_upload_task(app.clone(), watch.uid, watch.template.clone(), video).await
+    {
+        error!("监控文件加入上传队列失败: {:?} - {}", path, e);
+        // 文件不再变化时不会重新触发，通知前端由用户处理
+        let app_lock = app.state::<Mutex<AppData>>();
+        app_lock
+            .lock()
+            .await
+            .upload_service
+            .events()
+            .watch_rejected(&path.to_string_lossy(), watch.uid, &watch.template, e);
+        return;
+    }
+    info!("监控文件已加入上传队列: {:?} -> {}", path, watch.template);
+    remember(history, hash);
+
+    if watch.auto_start
+        && let Err(e) = commands::start_upload(app.clone(), task_id).await
+    {
+        error!("监控文件开始上传失败: {:?} - {}", path, e);
+    }
+}
+
+fn remember(history: &mut HashSet<String>, hash: String) {
+    history.insert(hash);
+    if let Err(e) = save_history(history) {
+        error!("保存文件夹监控记录失败: {}", e);
+    }
+}
+
+fn load_history() -> Result<HashSet<String>> {
+    let path = get_watch_history_json_path()?;
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn save_history(history: &HashSet<String>) -> Result<()> {
+    let content = serde_json::to_string(history)?;
+    let path = get_watch_history_json_path()?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// 查找以写方式打开该文件的进程，无权限查看的进程会被忽略
+#[cfg(target_os = "linux")]
+fn is_open_for_writing(path: &Path) -> bool {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return false;
+    };
+    for proc in procs.flatten() {
+        if !proc
+            .file_name()
+            .to_string_lossy()
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+        {
+            continue;
+        }
+        let Ok(fds) = fs::read_dir(proc.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if fs::read_link(fd.path()).is_ok_and(|target| target == path)
+                && fd_writable(&proc.path().join("fdinfo").join(fd.file_name()))
+            {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// fdinfo 中的 flags 为八进制，低两位是 O_WRONLY / O_RDWR
+#[cfg(target_os = "linux")]
+fn fd_writable(fdinfo: &Path) -> bool {
+    fs::read_to_string(fdinfo)
+        .ok()
+        .and_then(|info| {
+            info.lines()
+                .find_map(|line| line.strip_prefix("flags:"))
+                .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+        })
+        .is_some_and(|flags| flags & 0o3 != 0)
+}
+
+/// 只共享读权限打开文件，其他进程以写方式打开时会失败
+#[cfg(windows)]
+fn is_open_for_writing(path: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_SHARE_READ: u32 = 0x1;
+    fs::OpenOptions::new()
+        .read(true)
+        .share_mode(FILE_SHARE_READ)
+        .open(path)
+        .is_err()
+}
+
+/// 其他系统无法可靠判断，只依靠大小和修改时间
+#[cfg(not(any(target_os = "linux", windows)))]
+fn is_open_for_writing(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_filter_matches_video_extensions() {
+        assert!(matches_filter("", "live.mp4"));
+        assert!(matches_filter("  ", "LIVE.MKV"));
+        assert!(!matches_filter("", "notes.txt"));
+        assert!(!matches_filter("", "mp4"));
+    }
+
+    #[test]
+    fn extension_list_filter() {
+        assert!(matches_filter("flv, .ts", "record.TS"));
+        assert!(matches_filter("flv, .ts", "record.flv"));
+        assert!(!matches_filter("flv, .ts", "record.mp4"));
+    }
+
+    #[test]
+    fn glob_filter() {
+        assert!(matches_filter("*.mp4", "live.MP4"));
+        assert!(matches_filter("live_*.flv", "live_2024-01-01.flv"));
+        assert!(!matches_filter("live_*.flv", "replay_2024-01-01.flv"));
+        assert!(matches_filter("part?.mkv", "part1.mkv"));
+        assert!(!matches_filter("part?.mkv", "part10.mkv"));
+        assert!(matches_filter("[ab]*.ts", "a1.ts"));
+        assert!(!matches_filter("[ab]*.ts", "c1.ts"));
+        // 无效的模式不匹配任何文件
+        assert!(!matches_filter("[*.mp4", "a.mp4"));
+    }
+}
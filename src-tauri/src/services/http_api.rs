@@ -0,0 +1,311 @@
+use std::{collections::HashMap, convert::Infallible, net::Ipv4Addr, path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    Json, Router,
+    extract::{Path, Request, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{get, post},
+};
+use futures::Stream;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::Sha256;
+use tauri::{AppHandle, Manager};
+use tokio::{
+    sync::{Mutex, broadcast::error::RecvError},
+    task,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    AppData, commands,
+    models::{ApiConfig, TemplateConfig, UploadTask, User, VideoInfo},
+    utils::get_config_json_path,
+};
+
+/// 按当前配置重新启动本地接口，关闭时只停止旧的服务
+pub async fn restart(app: AppHandle) -> Result<()> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let mut app_data = app_lock.lock().await;
+    if let Some(handle) = app_data.api_server.take() {
+        handle.abort();
+        info!("本地接口已停止");
+    }
+
+    let api = {
+        let mut config = app_data.config.lock().await;
+        if config.api.ensure_token() {
+            config.save_to_file(&get_config_json_path()?)?;
+            info!("已生成本地接口令牌");
+        }
+        config.api.clone()
+    };
+    if api.enabled {
+        app_data.api_server = Some(start(app.clone(), &api).await?);
+    }
+    Ok(())
+}
+
+async fn start(app: AppHandle, api: &ApiConfig) -> Result<task::JoinHandle<()>> {
+    let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, api.port)).await?;
+    info!("本地接口已启动: http://{}", listener.local_addr()?);
+
+    let token = Arc::new(api.token.clone());
+    let router = Router::new()
+        .route("/api/users", get(users))
+        .route("/api/users/:uid/templates", get(templates))
+        .route("/api/queue", get(queue))
+        .route("/api/tasks", post(create_task))
+        .route("/api/tasks/:task_id/:action", post(task_action))
+        .route("/api/submit", post(submit))
+        .route("/api/events", get(events))
+        .route_layer(middleware::from_fn_with_state(token, authorize))
+        .with_state(app);
+
+    Ok(task::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!("本地接口异常退出: {}", e);
+        }
+    }))
+}
+
+/// 令牌可以放在 Authorization: Bearer 头中，EventSource 无法设置请求头时放在 token 查询参数中
+async fn authorize(State(token): State<Arc<String>>, request: Request, next: Next) -> Response {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+    });
+    let authorized = bearer
+        .or(query)
+        .is_some_and(|given| token_matches(&token, given));
+    if !authorized {
+        return ApiError(StatusCode::UNAUTHORIZED, "令牌无效".to_string()).into_response();
+    }
+    next.run(request).await
+}
+
+/// 以令牌为密钥计算同一段消息的 HMAC 并做常数时间比较，避免逐字节比较泄露令牌
+fn token_matches(token: &str, given: &str) -> bool {
+    const MESSAGE: &[u8] = b"bilibili-upload-api";
+    if token.is_empty() {
+        return false;
+    }
+    let Ok(mut expected) = Hmac::<Sha256>::new_from_slice(token.as_bytes()) else {
+        return false;
+    };
+    expected.update(MESSAGE);
+    let Ok(mut actual) = Hmac::<Sha256>::new_from_slice(given.as_bytes()) else {
+        return false;
+    };
+    actual.update(MESSAGE);
+    actual
+        .verify_slice(&expected.finalize().into_bytes())
+        .is_ok()
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// 命令返回的错误视为请求错误
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        Self(StatusCode::BAD_REQUEST, message)
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+async fn users(State(app): State<AppHandle>) -> ApiResult<Vec<User>> {
+    Ok(Json(commands::get_login_users(app).await?))
+}
+
+async fn templates(
+    State(app): State<AppHandle>,
+    Path(uid): Path<u64>,
+) -> ApiResult<HashMap<String, TemplateConfig>> {
+    let app_lock = app.state::<Mutex<AppData>>();
+    let app_data = app_lock.lock().await;
+    let config = app_data.config.lock().await;
+    let user_config = config
+        .config
+        .get(&uid)
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("账号未登录: {uid}")))?;
+    Ok(Json(user_config.templates.clone()))
+}
+
+async fn queue(State(app): State<AppHandle>) -> ApiResult<Vec<UploadTask>> {
+    Ok(Json(commands::get_upload_queue(app).await?))
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateTaskRequest {
+    uid: u64,
+    template: String,
+    path: String,
+    /// 分P标题，为空时取文件名
+    #[serde(default)]
+    title: Option<String>,
+    /// 创建后立即开始上传
+    #[serde(default)]
+    start: bool,
+}
+
+async fn create_task(
+    State(app): State<AppHandle>,
+    Json(request): Json<CreateTaskRequest>,
+) -> ApiResult<Value> {
+    let path = PathBuf::from(&request.path);
+    if !path.is_file() {
+        return Err(format!("文件不存在: {}", request.path).into());
+    }
+
+    let task_id = format!(
+        "api-{}-{}",
+        chrono::Utc::now().timestamp_millis(),
+        fastrand::u32(..)
+    );
+    let mut video = VideoInfo::from_path(task_id.clone(), &path);
+    if let Some(title) = request.title {
+        video.title = title;
+    }
+    commands::create_upload_task(app.clone(), request.uid, request.template, video).await?;
+    if request.start {
+        commands::start_upload(app, task_id.clone()).await?;
+    }
+    Ok(Json(json!({ "task_id": task_id })))
+}
+
+async fn task_action(
+    State(app): State<AppHandle>,
+    Path((task_id, action)): Path<(String, String)>,
+) -> ApiResult<bool> {
+    let result = match action.as_str() {
+        "start" => commands::start_upload(app, task_id).await?,
+        "pause" => commands::pause_upload(app, task_id).await?,
+        "cancel" => commands::cancel_upload(app, task_id).await?,
+        "retry" => commands::retry_upload(app, task_id).await?,
+        _ => {
+            return Err(ApiError(
+                StatusCode::NOT_FOUND,
+                format!("未知的操作: {action}"),
+            ));
+        }
+    };
+    Ok(Json(result))
+}
+
+#[derive(Debug, Deserialize)]
+struct SubmitRequest {
+    uid: u64,
+    template: String,
+    /// 分P对应的任务，按分P顺序
+    task_ids: Vec<String>,
+    /// 任务未完成时登记草稿，全部上传完成后由后台投稿
+    #[serde(default)]
+    wait: bool,
+}
+
+async fn submit(
+    State(app): State<AppHandle>,
+    Json(request): Json<SubmitRequest>,
+) -> ApiResult<Value> {
+    if request.wait {
+        let draft =
+            commands::create_draft(app, request.uid, request.template, request.task_ids).await?;
+        return Ok(Json(json!(draft)));
+    }
+
+    let form = {
+        let app_lock = app.state::<Mutex<AppData>>();
+        let app_data = app_lock.lock().await;
+        let mut form = app_data
+            .config
+            .lock()
+            .await
+            .config
+            .get(&request.uid)
+            .and_then(|user_config| user_config.templates.get(&request.template).cloned())
+            .ok_or_else(|| format!("模板不存在: {}", request.template))?;
+        let tasks = app_data
+            .upload_service
+            .tasks(&request.task_ids)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        form.videos.clear();
+        for task_mutex in tasks {
+            let task = task_mutex.lock().await;
+            if !task.is_completed() {
+                return Err(ApiError(
+                    StatusCode::CONFLICT,
+                    format!("任务尚未上传完成: {}", task.title()),
+                ));
+            }
+            form.videos.push(task.video.clone());
+        }
+        form
+    };
+    let data = commands::submit(app, request.uid, form, Some(request.template)).await?;
+    Ok(Json(data))
+}
+
+/// 以 SSE 推送上传事件，事件名与前端监听的相同
+async fn events(
+    State(app): State<AppHandle>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = {
+        let app_lock = app.state::<Mutex<AppData>>();
+        let app_data = app_lock.lock().await;
+        app_data.upload_service.events().subscribe()
+    };
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        let event = match rx.recv().await {
+            Ok(event) => Event::default()
+                .event(event.name())
+                .json_data(&event)
+                .unwrap_or_default(),
+            // 消费过慢丢失了事件，客户端应重新读取队列
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("本地接口事件订阅落后，丢弃 {} 个事件", skipped);
+                Event::default()
+                    .event("upload://lagged")
+                    .data(skipped.to_string())
+            }
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok(event), rx))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::token_matches;
+
+    #[test]
+    fn token_must_match_exactly() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secret", "secre"));
+        assert!(!token_matches("secret", "secret2"));
+        assert!(!token_matches("secret", ""));
+        assert!(!token_matches("", ""));
+    }
+}
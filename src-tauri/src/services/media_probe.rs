@@ -0,0 +1,214 @@
+use std::path::Path;
+
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{
+    models::{MediaProbe, ProbeConfig},
+    utils::{ffmpeg, get_file_size},
+};
+
+/// 调用 ffprobe 读取媒体信息，未安装 ffprobe 时返回 None
+pub async fn probe(path: &str) -> Result<Option<MediaProbe>, String> {
+    let args = [
+        "-v",
+        "error",
+        "-print_format",
+        "json",
+        "-show_format",
+        "-show_streams",
+        path,
+    ]
+    .map(String::from)
+    .to_vec();
+    let output = match ffmpeg::run("ffprobe", args).await {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            warn!("{}，跳过视频检查", ffmpeg::spawn_error("ffprobe", &e));
+            return Ok(None);
+        }
+        Err(e) => return Err(ffmpeg::spawn_error("ffprobe", &e)),
+    };
+    if !output.status.success() {
+        let reason = ffmpeg::last_error_line(&output).unwrap_or_else(|| "未知错误".to_string());
+        return Err(format!("文件头损坏或格式无法识别: {reason}"));
+    }
+
+    let res: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("解析 ffprobe 输出失败: {e}"))?;
+    // ffprobe 的数值字段多为字符串
+    let number = |v: &Value| {
+        v.as_str()
+            .and_then(|s| s.parse::<f64>().ok())
+            .or_else(|| v.as_f64())
+    };
+    let streams = res["streams"].as_array().cloned().unwrap_or_default();
+    // 封面图也是视频流，需要排除
+    let video = streams
+        .iter()
+        .find(|s| s["codec_type"] == "video" && s["disposition"]["attached_pic"] != 1);
+    let audio = streams.iter().find(|s| s["codec_type"] == "audio");
+
+    Ok(Some(MediaProbe {
+        format: res["format"]["format_name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        duration: number(&res["format"]["duration"]).unwrap_or(0.0),
+        width: video.and_then(|s| s["width"].as_u64()).unwrap_or(0) as u32,
+        height: video.and_then(|s| s["height"].as_u64()).unwrap_or(0) as u32,
+        video_codec: video.and_then(|s| s["codec_name"].as_str().map(String::from)),
+        audio_codec: audio.and_then(|s| s["codec_name"].as_str().map(String::from)),
+        bit_rate: number(&res["format"]["bit_rate"]).unwrap_or(0.0) as u64,
+        warnings: Vec::new(),
+    }))
+}
+
+/// 按配置检查视频文件，发现问题时根据配置拒绝或记录为警告。
+/// 桌面端、命令行、HTTP 接口和文件夹监控创建任务时都会检查
+pub async fn check(path: &str, config: &ProbeConfig) -> Result<Option<MediaProbe>, String> {
+    if !config.enabled {
+        return Ok(None);
+    }
+
+    let size = get_file_size(Path::new(path)).map_err(|e| format!("读取文件失败: {e}"))?;
+    evaluate(path, size, probe(path).await, config)
+}
+
+/// 根据文件大小和 ffprobe 的结果判断是否通过检查
+fn evaluate(
+    path: &str,
+    size: u64,
+    probed: Result<Option<MediaProbe>, String>,
+    config: &ProbeConfig,
+) -> Result<Option<MediaProbe>, String> {
+    let mut issues = Vec::new();
+    if config.max_size > 0 && size > config.max_size {
+        issues.push(format!(
+            "文件大小 {:.2} GB 超过上限 {:.2} GB",
+            size as f64 / (1u64 << 30) as f64,
+            config.max_size as f64 / (1u64 << 30) as f64
+        ));
+    }
+
+    let mut media = match probed {
+        Ok(Some(media)) => media,
+        Ok(None) if issues.is_empty() => return Ok(None),
+        Ok(None) => MediaProbe::default(),
+        Err(e) => {
+            issues.push(e);
+            MediaProbe::default()
+        }
+    };
+    if !media.format.is_empty() {
+        if media.video_codec.is_none() {
+            issues.push("没有视频流".to_string());
+        }
+        if media.duration <= 0.0 {
+            issues.push("视频时长为 0".to_string());
+        } else if config.max_duration_secs > 0 && media.duration > config.max_duration_secs as f64 {
+            issues.push(format!(
+                "视频时长 {:.0} 秒超过上限 {} 秒",
+                media.duration, config.max_duration_secs
+            ));
+        }
+    }
+
+    if !issues.is_empty() {
+        let message = issues.join("；");
+        if config.reject {
+            return Err(format!("视频检查未通过: {message}"));
+        }
+        warn!("视频检查发现问题: {} - {}", path, message);
+        media.warnings = issues;
+    } else {
+        info!(
+            "视频检查通过: {} ({}x{}, {:.0} 秒, {:?}/{:?})",
+            path, media.width, media.height, media.duration, media.video_codec, media.audio_codec
+        );
+    }
+    Ok(Some(media))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1 << 30;
+
+    fn config(reject: bool) -> ProbeConfig {
+        ProbeConfig {
+            enabled: true,
+            reject,
+            max_size: 4 * GIB,
+            max_duration_secs: 3600,
+        }
+    }
+
+    fn media(duration: f64) -> MediaProbe {
+        MediaProbe {
+            format: "mov,mp4,m4a,3gp,3g2,mj2".to_string(),
+            duration,
+            width: 1920,
+            height: 1080,
+            video_codec: Some("h264".to_string()),
+            audio_codec: Some("aac".to_string()),
+            bit_rate: 6_000_000,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn passes_within_limits() {
+        let result = evaluate("a.mp4", GIB, Ok(Some(media(600.0))), &config(true));
+        assert_eq!(result, Ok(Some(media(600.0))));
+    }
+
+    #[test]
+    fn rejects_over_limits() {
+        let err = evaluate("a.mp4", 5 * GIB, Ok(Some(media(600.0))), &config(true)).unwrap_err();
+        assert!(err.contains("文件大小"), "{err}");
+
+        let err = evaluate("a.mp4", GIB, Ok(Some(media(7200.0))), &config(true)).unwrap_err();
+        assert!(err.contains("视频时长"), "{err}");
+
+        let mut audio_only = media(600.0);
+        audio_only.video_codec = None;
+        let err = evaluate("a.mp4", GIB, Ok(Some(audio_only)), &config(true)).unwrap_err();
+        assert!(err.contains("没有视频流"), "{err}");
+
+        let err = evaluate("a.mp4", GIB, Err("文件头损坏".to_string()), &config(true)).unwrap_err();
+        assert!(err.contains("文件头损坏"), "{err}");
+    }
+
+    #[test]
+    fn warns_instead_of_rejecting() {
+        let media = evaluate("a.mp4", 5 * GIB, Ok(Some(media(7200.0))), &config(false))
+            .unwrap()
+            .unwrap();
+        assert_eq!(media.warnings.len(), 2);
+    }
+
+    #[test]
+    fn zero_limits_are_unchecked() {
+        let config = ProbeConfig {
+            max_size: 0,
+            max_duration_secs: 0,
+            ..config(true)
+        };
+        assert!(evaluate("a.mp4", 100 * GIB, Ok(Some(media(86400.0))), &config).is_ok());
+    }
+
+    #[test]
+    fn skips_without_ffprobe() {
+        // 未安装 ffprobe 时只检查文件大小
+        assert_eq!(evaluate("a.mp4", GIB, Ok(None), &config(true)), Ok(None));
+        assert!(evaluate("a.mp4", 5 * GIB, Ok(None), &config(true)).is_err());
+
+        let media = evaluate("a.mp4", 5 * GIB, Ok(None), &config(false))
+            .unwrap()
+            .unwrap();
+        assert_eq!(media.format, "");
+        assert_eq!(media.warnings.len(), 1);
+    }
+}
@@ -1,4 +1,16 @@
 pub mod auth_service;
+pub mod folder_watcher;
+pub mod http_api;
+pub mod media_probe;
+pub mod publish_ledger;
+pub mod rate_limiter;
+pub mod submit_pipeline;
+pub mod template_render;
+pub mod template_validator;
+pub mod translation;
+pub mod upload_events;
 pub mod upload_service;
+pub mod upos;
+pub mod webhook;
 
 pub use auth_service::*;
@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::Result;
+use biliup::bilibili::Studio;
+use chrono::TimeZone;
+use serde_json::Value;
+use tokio::task;
+use tracing::{error, info, warn};
+
+use crate::{
+    models::{
+        LedgerQuery, PublishAction, PublishRecord, PublishedPart, SourceFingerprint, VideoInfo,
+    },
+    utils::get_publish_ledger_path,
+};
+
+/// 投稿成功后记入账本，写入失败只记录日志，不影响投稿结果。
+/// sources 为上传任务记录的源文件指纹，按任务ID索引，上传完成后分P的路径已被清空
+pub async fn record(
+    uid: u64,
+    template: &str,
+    action: PublishAction,
+    videos: &[VideoInfo],
+    sources: &HashMap<String, SourceFingerprint>,
+    studio: &Studio,
+    response: &Value,
+) {
+    let parts = videos
+        .iter()
+        .map(|video| published_part(video, sources.get(&video.id)))
+        .collect();
+    let record = PublishRecord {
+        uid,
+        template: template.to_string(),
+        action,
+        aid: response["aid"].as_u64(),
+        bvid: response["bvid"].as_str().map(String::from),
+        title: studio.title.clone(),
+        parts,
+        form: serde_json::to_value(studio).unwrap_or_default(),
+        response: response.clone(),
+        published_at: chrono::Utc::now().timestamp_millis() as usize,
+    };
+    match task::spawn_blocking(move || append(&record)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("写入投稿账本失败: {}", e),
+        Err(e) => error!("写入投稿账本失败: {}", e),
+    }
+}
+
+fn published_part(video: &VideoInfo, source: Option<&SourceFingerprint>) -> PublishedPart {
+    PublishedPart {
+        title: video.title.clone(),
+        filename: video.filename.clone(),
+        cid: video.cid,
+        path: source.map_or_else(|| video.path.clone(), |source| source.path.clone()),
+        hash: source.map(|source| source.partial_hash.clone()),
+    }
+}
+
+fn append(record: &PublishRecord) -> Result<()> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(get_publish_ledger_path()?)?;
+    file.write_all(line.as_bytes())?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// 按条件查询账本，最新的记录在前
+pub fn query(query: &LedgerQuery) -> Result<Vec<PublishRecord>> {
+    let path = get_publish_ledger_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    let mut records = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<PublishRecord>(line) {
+            Ok(record) if query.matches(&record) => records.push(record),
+            Ok(_) => {}
+            // 写入中断留下的半行不影响其他记录
+            Err(e) => warn!("跳过无法解析的账本记录: 第 {} 行 - {}", index + 1, e),
+        }
+    }
+    records.reverse();
+    Ok(records)
+}
+
+/// 把查询结果导出为 CSV，每个分P一行，返回导出的记录数
+pub fn export_csv(query: &LedgerQuery, path: &Path) -> Result<usize> {
+    let records = self::query(query)?;
+    let mut content = String::from(
+        "published_at,uid,template,action,aid,bvid,title,part,part_title,filename,cid,path,hash\n",
+    );
+    for record in &records {
+        let published_at = chrono::Local
+            .timestamp_millis_opt(record.published_at as i64)
+            .single()
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        for (index, part) in record.parts.iter().enumerate() {
+            let fields = [
+                published_at.clone(),
+                record.uid.to_string(),
+                record.template.clone(),
+                format!("{:?}", record.action),
+                record.aid.map(|aid| aid.to_string()).unwrap_or_default(),
+                record.bvid.clone().unwrap_or_default(),
+                record.title.clone(),
+                (index + 1).to_string(),
+                part.title.clone(),
+                part.filename.clone(),
+                part.cid.to_string(),
+                part.path.clone(),
+                part.hash.clone().unwrap_or_default(),
+            ];
+            let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            content.push_str(&row.join(","));
+            content.push('\n');
+        }
+    }
+    // 带 BOM 方便 Excel 识别 UTF-8
+    fs::write(path, format!("\u{feff}{content}"))?;
+    info!("导出投稿账本: {} 条 -> {:?}", records.len(), path);
+    Ok(records.len())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_field_quotes_special_characters() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(csv_field("cr\rlf"), "\"cr\rlf\"");
+    }
+
+    #[test]
+    fn published_part_prefers_source_fingerprint() {
+        let mut video = VideoInfo::from_path("task-1".to_string(), Path::new(""));
+        video.title = "P1".to_string();
+        let source = SourceFingerprint {
+            path: "/videos/a.mp4".to_string(),
+            size: 1,
+            mtime: 0,
+            partial_hash: "abc".to_string(),
+        };
+
+        let part = published_part(&video, Some(&source));
+        assert_eq!(part.path, "/videos/a.mp4");
+        assert_eq!(part.hash.as_deref(), Some("abc"));
+
+        let part = published_part(&video, None);
+        assert_eq!(part.path, "");
+        assert_eq!(part.hash, None);
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::debug;
+
+use crate::models::ConfigRoot;
+
+/// 单次等待的最长时间，限速调整后能尽快生效
+const MAX_WAIT: Duration = Duration::from_millis(200);
+
+struct Bucket {
+    rate: u64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        // 桶容量为一秒的流量
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.rate as f64);
+    }
+
+    /// 取出 n 字节的令牌，不足时返回需要等待的时间
+    fn take(&mut self, n: u64, now: Instant) -> Option<Duration> {
+        if self.rate == 0 {
+            return None;
+        }
+
+        self.refill(now);
+        // 单次请求超过桶容量时，桶满即可放行，余额允许为负
+        let needed = (n as f64).min(self.rate as f64);
+        if self.tokens >= needed {
+            self.tokens -= n as f64;
+            None
+        } else {
+            let wait = (needed - self.tokens) / self.rate as f64;
+            Some(Duration::from_secs_f64(wait).min(MAX_WAIT))
+        }
+    }
+}
+
+/// 令牌桶限速器，速率单位为字节每秒，0 表示不限速
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            bucket: Mutex::new(Bucket {
+                rate,
+                tokens: rate as f64,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn rate(&self) -> u64 {
+        self.bucket.lock().map(|bucket| bucket.rate).unwrap_or(0)
+    }
+
+    pub fn set_rate(&self, rate: u64) {
+        if let Ok(mut bucket) = self.bucket.lock() {
+            bucket.refill(Instant::now());
+            bucket.rate = rate;
+            bucket.tokens = bucket.tokens.min(rate as f64);
+        }
+    }
+
+    /// 尝试取出 n 字节的令牌，不足时返回需要等待的时间
+    fn try_acquire(&self, n: u64) -> Option<Duration> {
+        self.bucket.lock().ok()?.take(n, Instant::now())
+    }
+
+    pub async fn acquire(&self, n: u64) {
+        while let Some(wait) = self.try_acquire(n) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// 全局和各账号的带宽限制，所有上传分片共享
+pub struct BandwidthLimits {
+    global: Arc<RateLimiter>,
+    accounts: Mutex<HashMap<u64, Arc<RateLimiter>>>,
+}
+
+impl Default for BandwidthLimits {
+    fn default() -> Self {
+        Self {
+            global: Arc::new(RateLimiter::new(0)),
+            accounts: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl BandwidthLimits {
+    fn account(&self, uid: u64) -> Arc<RateLimiter> {
+        let mut accounts = self.accounts.lock().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(
+            accounts
+                .entry(uid)
+                .or_insert_with(|| Arc::new(RateLimiter::new(0))),
+        )
+    }
+
+    /// 某个账号上传时需要经过的限速器
+    pub fn limiters_for(&self, uid: u64) -> Vec<Arc<RateLimiter>> {
+        vec![Arc::clone(&self.global), self.account(uid)]
+    }
+
+    pub fn global_rate(&self) -> u64 {
+        self.global.rate()
+    }
+
+    pub fn set_global(&self, rate: u64) {
+        debug!("更新全局带宽限制: {} B/s", rate);
+        self.global.set_rate(rate);
+    }
+
+    pub fn set_account(&self, uid: u64, rate: u64) {
+        debug!("更新账号 {} 带宽限制: {} B/s", uid, rate);
+        self.account(uid).set_rate(rate);
+    }
+
+    /// 按配置文件更新全部限速
+    pub fn apply_config(&self, config: &ConfigRoot) {
+        self.set_global(config.bandwidth_limit);
+        for (uid, user_config) in &config.config {
+            self.set_account(*uid, user_config.bandwidth_limit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(rate: u64, now: Instant) -> Bucket {
+        Bucket {
+            rate,
+            tokens: rate as f64,
+            last: now,
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn unlimited_never_waits() {
+        let now = Instant::now();
+        let mut bucket = bucket(0, now);
+        assert_eq!(bucket.take(u64::MAX, now), None);
+        assert_eq!(bucket.take(u64::MAX, now), None);
+    }
+
+    #[test]
+    fn refills_at_rate_up_to_capacity() {
+        let now = Instant::now();
+        let mut bucket = bucket(1000, now);
+        assert_eq!(bucket.take(1000, now), None);
+        // 桶已空，缺 100 字节需要等 0.1 秒
+        assert_eq!(bucket.take(100, now), Some(ms(100)));
+
+        assert_eq!(bucket.take(300, now + ms(300)), None);
+        assert!(bucket.tokens.abs() < 1e-6);
+
+        // 空闲很久后最多攒下一秒的令牌
+        bucket.refill(now + Duration::from_secs(60));
+        assert_eq!(bucket.tokens, 1000.0);
+    }
+
+    #[test]
+    fn wait_is_capped() {
+        let now = Instant::now();
+        let mut bucket = bucket(1000, now);
+        assert_eq!(bucket.take(1000, now), None);
+        assert_eq!(bucket.take(900, now), Some(MAX_WAIT));
+    }
+
+    #[test]
+    fn oversized_request_needs_full_bucket() {
+        let now = Instant::now();
+        let mut bucket = bucket(1000, now);
+        assert_eq!(bucket.take(5000, now), None);
+        assert_eq!(bucket.tokens, -4000.0);
+        // 透支的部分按速率补回后才放行下一次请求
+        assert_eq!(bucket.take(1, now + Duration::from_secs(4)), Some(ms(1)));
+        assert_eq!(bucket.take(1, now + ms(4001)), None);
+    }
+
+    #[test]
+    fn lowering_rate_drops_extra_tokens() {
+        let limiter = RateLimiter::new(1000);
+        limiter.set_rate(10);
+        assert_eq!(limiter.rate(), 10);
+        assert!(limiter.try_acquire(100).is_none());
+        assert!(limiter.try_acquire(10).is_some());
+    }
+
+    #[test]
+    fn accounts_share_the_global_limiter() {
+        let limits = BandwidthLimits::default();
+        limits.set_global(2000);
+        limits.set_account(7, 500);
+        assert_eq!(limits.global_rate(), 2000);
+
+        let first = limits.limiters_for(7);
+        let rates: Vec<u64> = first.iter().map(|l| l.rate()).collect();
+        assert_eq!(rates, [2000, 500]);
+        assert!(Arc::ptr_eq(&first[0], &limits.limiters_for(8)[0]));
+        assert!(Arc::ptr_eq(&first[1], &limits.limiters_for(7)[1]));
+        assert_eq!(limits.limiters_for(8)[1].rate(), 0);
+    }
+}
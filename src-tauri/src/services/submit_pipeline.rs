@@ -0,0 +1,443 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use biliup::{
+    bilibili::{BiliBili, Studio},
+    uploader::bilibili::Vid,
+};
+use indexmap::IndexMap;
+use serde_json::Value;
+use tokio::{sync::Mutex, task};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::{
+    models::{
+        Draft, DraftStatus, PublishAction, SourceFingerprint, TemplateConfig, UploadTask, VideoInfo,
+    },
+    services::{
+        publish_ledger, template_render, template_validator,
+        upload_events::UploadEvents,
+        upload_service::{UploadQueue, check_groups, check_source, source_map},
+        upos::LoginRequired,
+    },
+    utils::get_drafts_json_path,
+};
+
+/// 跟踪每次投稿的模板和上传任务，分P全部上传完成后在后台投稿，不依赖前端窗口
+#[derive(Clone, Default)]
+pub struct SubmitPipeline {
+    drafts: Arc<Mutex<IndexMap<String, Draft>>>,
+}
+
+impl SubmitPipeline {
+    /// 从磁盘恢复草稿，上次退出时正在投稿的草稿重新等待
+    pub async fn restore(&self) -> Result<usize> {
+        let path = get_drafts_json_path()?;
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        let drafts: Vec<Draft> = serde_json::from_str(&content)?;
+        let mut map = self.drafts.lock().await;
+        for mut draft in drafts {
+            if draft.status == DraftStatus::Submitting {
+                draft.status = DraftStatus::Uploading;
+            }
+            map.insert(draft.id.clone(), draft);
+        }
+        info!("已恢复 {} 个投稿草稿", map.len());
+        Ok(map.len())
+    }
+
+    async fn save(&self) {
+        let drafts: Vec<Draft> = self.drafts.lock().await.values().cloned().collect();
+        if let Err(e) = write_drafts(&drafts) {
+            error!("保存投稿草稿失败: {}", e);
+        }
+    }
+
+    pub async fn add(&self, draft: Draft, events: &UploadEvents) {
+        info!(
+            "创建投稿草稿: {} ({} 个分P)",
+            draft.id,
+            draft.task_ids.len()
+        );
+        events.draft_updated(&draft);
+        self.drafts.lock().await.insert(draft.id.clone(), draft);
+        self.save().await;
+    }
+
+    pub async fn list(&self) -> Vec<Draft> {
+        self.drafts.lock().await.values().cloned().collect()
+    }
+
+    pub async fn remove(&self, draft_id: &str) -> Option<Draft> {
+        let removed = self.drafts.lock().await.shift_remove(draft_id);
+        if removed.is_some() {
+            self.save().await;
+        }
+        removed
+    }
+
+    async fn update(&self, draft_id: &str, events: &UploadEvents, f: impl FnOnce(&mut Draft)) {
+        {
+            let mut drafts = self.drafts.lock().await;
+            let Some(draft) = drafts.get_mut(draft_id) else {
+                return;
+            };
+            f(draft);
+            events.draft_updated(draft);
+        }
+        self.save().await;
+    }
+
+    /// 检查等待中的草稿，分P全部上传完成时开始投稿；分P失败时记录原因继续等待手动重试。
+    /// auto_submit 对应全局的 auto_upload，关闭时上传完成的草稿继续等待，追加分P的草稿除外
+    pub async fn tick(&self, queue: &UploadQueue, events: &UploadEvents, auto_submit: bool) {
+        let waiting: Vec<Draft> = self
+            .drafts
+            .lock()
+            .await
+            .values()
+            .filter(|draft| draft.is_uploading())
+            .cloned()
+            .collect();
+
+        for draft in waiting {
+            let mut tasks = Vec::with_capacity(draft.task_ids.len());
+            let mut missing = None;
+            for task_id in &draft.task_ids {
+                match queue.lock().await.get(task_id) {
+                    Some(task_mutex) => tasks.push(Arc::clone(task_mutex)),
+                    None => {
+                        missing = Some(task_id.clone());
+                        break;
+                    }
+                }
+            }
+            if let Some(task_id) = missing {
+                warn!("投稿草稿 {} 的上传任务已被移除: {}", draft.id, task_id);
+                self.update(&draft.id, events, |draft| {
+                    draft.fail(format!("上传任务已被移除: {task_id}"))
+                })
+                .await;
+                continue;
+            }
+
+            let mut completed = 0;
+            let mut failed = None;
+            for task_mutex in &tasks {
+                let task = task_mutex.lock().await;
+                if task.is_completed() {
+                    completed += 1;
+                } else if task.is_failed() && failed.is_none() {
+                    // 自动重试期间任务为等待状态，失败状态需要手动重试
+                    failed = Some(format!(
+                        "分P上传失败: {} - {}",
+                        task.title(),
+                        task.error_message.as_deref().unwrap_or("未知错误")
+                    ));
+                }
+            }
+            if let Some(message) = failed {
+                // 草稿保持上传中，分P重试成功后继续投稿
+                if draft.error_message.as_deref() != Some(message.as_str()) {
+                    warn!("投稿草稿 {} 等待重试: {}", draft.id, message);
+                    self.update(&draft.id, events, |draft| {
+                        draft.error_message = Some(message)
+                    })
+                    .await;
+                }
+                continue;
+            }
+            if completed < tasks.len() {
+                trace!(
+                    "投稿草稿 {} 上传中: {}/{}",
+                    draft.id,
+                    completed,
+                    tasks.len()
+                );
+                continue;
+            }
+            // 同组分P可能不在该草稿中，需等整组上传完成后再投稿
+            if let Err(e) = check_groups(queue, &tasks).await {
+                trace!("投稿草稿 {} 等待分组: {}", draft.id, e);
+                continue;
+            }
+            if !should_submit(&draft, auto_submit) {
+                trace!("未开启自动投稿，草稿 {} 等待中", draft.id);
+                continue;
+            }
+
+            self.update(&draft.id, events, Draft::submitting).await;
+            let pipeline = self.clone();
+            let events = events.clone();
+            task::spawn(async move {
+                let result = submit_draft(&draft, &tasks).await;
+                events.submit_result(
+                    draft.uid,
+                    &draft.template,
+                    &result.as_ref().cloned().map_err(|e| e.to_string()),
+                );
+                match result {
+                    Ok(data) => {
+                        let aid = data["aid"].as_u64();
+                        let bvid = data["bvid"].as_str().map(String::from);
+                        info!("自动投稿成功: {} -> {:?} {:?}", draft.id, aid, bvid);
+                        pipeline
+                            .update(&draft.id, &events, |draft| draft.submitted(aid, bvid))
+                            .await;
+                    }
+                    Err(e) => {
+                        error!("自动投稿失败: {} - {}", draft.id, e);
+                        pipeline
+                            .update(&draft.id, &events, |draft| draft.fail(e.to_string()))
+                            .await;
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// 追加分P由用户单独发起，不受自动投稿开关影响
+fn should_submit(draft: &Draft, auto_submit: bool) -> bool {
+    auto_submit || draft.append_to.is_some()
+}
+
+fn write_drafts(drafts: &[Draft]) -> Result<()> {
+    let content = serde_json::to_string_pretty(drafts)?;
+    let path = get_drafts_json_path()?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, content)?;
+    std::fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// 用模板和上传完成的分P生成投稿表单并提交，模板已有 aid 时编辑稿件
+pub(crate) async fn submit_draft(draft: &Draft, tasks: &[Arc<Mutex<UploadTask>>]) -> Result<Value> {
+    let first = tasks
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("投稿草稿没有分P"))?;
+    let (config, clients) = {
+        let task = first.lock().await;
+        (Arc::clone(task.config()), Arc::clone(task.clients()))
+    };
+
+    let mut videos = Vec::with_capacity(tasks.len());
+    for task_mutex in tasks {
+        check_source(task_mutex).await?;
+        videos.push(task_mutex.lock().await.video.clone());
+    }
+    let sources = source_map(tasks).await;
+
+    let (template, proxy, account) = {
+        let config = config.lock().await;
+        let user_config = config
+            .config
+            .get(&draft.uid)
+            .ok_or_else(|| LoginRequired("用户未登录或不存在".to_string()))?;
+        let template = match draft.append_to {
+            Some(_) => None,
+            None => Some(
+                user_config
+                    .templates
+                    .get(&draft.template)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("模板不存在: {}", draft.template))?,
+            ),
+        };
+        (
+            template,
+            user_config.proxy.clone(),
+            user_config.user.name.clone(),
+        )
+    };
+
+    let bilibili = clients
+        .lock()
+        .await
+        .get(&draft.uid)
+        .ok_or_else(|| LoginRequired("用户未登录或不存在".to_string()))?
+        .bilibili
+        .clone();
+
+    let Some(mut template) = template else {
+        let aid = draft.append_to.unwrap_or_default();
+        return append_videos(
+            &bilibili,
+            draft.uid,
+            aid,
+            videos,
+            &sources,
+            proxy.as_deref(),
+        )
+        .await;
+    };
+    template.videos = videos;
+    template_render::apply(&mut template, &account, &sources)?;
+    let report = template_validator::validate(&template);
+    report.ensure_valid()?;
+    let template = report.fixed;
+    let videos = template.videos.clone();
+    let aid = template.aid;
+    let studio = template.into_bilibili_form().try_into_studio()?;
+    debug!("自动投稿表单: {}", serde_json::to_string(&studio)?);
+
+    let (action, data) = if let Some(aid) = aid {
+        (
+            PublishAction::Edit,
+            edit_archive(&bilibili, &studio, aid).await?,
+        )
+    } else {
+        let resp = bilibili.submit_by_app(&studio, proxy.as_deref()).await?;
+        let data = resp.data.ok_or_else(|| anyhow::anyhow!("返回值错误"))?;
+        (PublishAction::Submit, data)
+    };
+    publish_ledger::record(
+        draft.uid,
+        &draft.template,
+        action,
+        &videos,
+        &sources,
+        &studio,
+        &data,
+    )
+    .await;
+    Ok(data)
+}
+
+/// 读取已发布的稿件，新分P接在原有分P之后，原有分P保留 cid，只编辑一次稿件
+async fn append_videos(
+    bilibili: &BiliBili,
+    uid: u64,
+    aid: u64,
+    videos: Vec<VideoInfo>,
+    sources: &HashMap<String, SourceFingerprint>,
+    proxy: Option<&str>,
+) -> Result<Value> {
+    let res = bilibili.video_data(&Vid::Aid(aid), proxy).await?;
+    let mut template = TemplateConfig::from_bilibili_res(res)?;
+    // 与 get_video_detail 一样，以公开接口返回的简介为准
+    match fetch_desc(bilibili, aid).await {
+        Ok(desc) if !desc.is_empty() => template.desc = desc,
+        Ok(_) => {}
+        Err(e) => warn!("获取稿件描述失败: av{} - {}", aid, e),
+    }
+
+    let existing = template.videos.len();
+    if merge_parts(&mut template.videos, videos) == 0 {
+        anyhow::bail!("分P已全部在稿件中: av{}", aid);
+    }
+    info!(
+        "追加分P: av{} {} -> {}",
+        aid,
+        existing,
+        template.videos.len()
+    );
+
+    template.aid = Some(aid);
+    let videos = template.videos.clone();
+    let studio = template.into_bilibili_form().try_into_studio()?;
+    debug!("追加分P表单: {}", serde_json::to_string(&studio)?);
+    let data = edit_archive(bilibili, &studio, aid).await?;
+    publish_ledger::record(
+        uid,
+        "",
+        PublishAction::Append,
+        &videos,
+        sources,
+        &studio,
+        &data,
+    )
+    .await;
+    Ok(data)
+}
+
+/// 把新分P接在原有分P之后，返回实际追加的数量
+fn merge_parts(parts: &mut Vec<VideoInfo>, videos: Vec<VideoInfo>) -> usize {
+    let existing = parts.len();
+    for video in videos {
+        // 上次编辑成功但未记录结果时，重试不会重复追加
+        if parts
+            .iter()
+            .any(|v| !video.filename.is_empty() && v.filename == video.filename)
+        {
+            continue;
+        }
+        parts.push(video);
+    }
+    parts.len() - existing
+}
+
+async fn fetch_desc(bilibili: &BiliBili, aid: u64) -> Result<String> {
+    let res: Value = bilibili
+        .client
+        .get(format!(
+            "https://api.bilibili.com/x/web-interface/view?aid={aid}"
+        ))
+        .send()
+        .await?
+        .json()
+        .await?;
+    Ok(res["data"]["desc"].as_str().unwrap_or("").to_string())
+}
+
+async fn edit_archive(bilibili: &BiliBili, studio: &Studio, aid: u64) -> Result<Value> {
+    let resp = bilibili.edit_by_web(studio).await?;
+    let mut data = resp["data"].clone();
+    if data["aid"].is_null() {
+        data["aid"] = aid.into();
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn part(filename: &str, cid: u64) -> VideoInfo {
+        let mut video = VideoInfo::from_path(filename.to_string(), Path::new(""));
+        video.filename = filename.to_string();
+        video.cid = cid;
+        video
+    }
+
+    #[test]
+    fn append_drafts_ignore_auto_submit() {
+        let draft = Draft::new(1, "模板", vec!["task-1".to_string()]);
+        assert!(!should_submit(&draft, false));
+        assert!(should_submit(&draft, true));
+
+        let draft = Draft::append(1, 100, vec!["task-1".to_string()]);
+        assert_eq!(draft.append_to, Some(100));
+        assert!(draft.is_uploading());
+        assert!(should_submit(&draft, false));
+    }
+
+    #[test]
+    fn appends_new_parts_after_existing() {
+        let mut parts = vec![part("p1", 11), part("p2", 12)];
+        let appended = merge_parts(&mut parts, vec![part("p3", 0), part("p4", 0)]);
+        assert_eq!(appended, 2);
+        let filenames: Vec<&str> = parts.iter().map(|v| v.filename.as_str()).collect();
+        assert_eq!(filenames, ["p1", "p2", "p3", "p4"]);
+        // 原有分P保留 cid
+        assert_eq!(parts[0].cid, 11);
+        assert_eq!(parts[1].cid, 12);
+    }
+
+    #[test]
+    fn skips_parts_already_in_archive() {
+        let mut parts = vec![part("p1", 11), part("p2", 12)];
+        assert_eq!(merge_parts(&mut parts, vec![part("p2", 0)]), 0);
+        assert_eq!(parts.len(), 2);
+
+        // 没有文件名的分P无法判断是否重复，总是追加
+        assert_eq!(merge_parts(&mut parts, vec![part("", 0)]), 1);
+        assert_eq!(parts.len(), 3);
+    }
+}
@@ -0,0 +1,412 @@
+//! 投稿模板中的变量，如 `{stem}`、`{date:%Y-%m-%d}`、`{filename|truncate(20)|upper}`。
+//! `{{` 和 `}}` 输出花括号，不认识的变量原样保留，兼容标题中本来就有的花括号。
+
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Local, TimeZone};
+use serde::Serialize;
+
+use crate::models::{SourceFingerprint, TemplateConfig, VideoInfo};
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const DEFAULT_MTIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// 渲染某个分P时可用的变量
+#[derive(Debug, Clone)]
+pub struct RenderContext {
+    pub filename: String,
+    pub stem: String,
+    pub ext: String,
+    pub mtime: DateTime<Local>,
+    pub now: DateTime<Local>,
+    /// 分P序号，从 1 开始
+    pub index: usize,
+    pub part_count: usize,
+    /// 时长（秒），没有 ffprobe 结果时为空
+    pub duration: Option<f64>,
+    pub account: String,
+}
+
+impl RenderContext {
+    /// source 为上传任务记录的源文件，上传完成后分P的路径已被清空，有记录时以其为准
+    pub fn for_video(
+        video: &VideoInfo,
+        source: Option<&SourceFingerprint>,
+        index: usize,
+        part_count: usize,
+        account: &str,
+    ) -> Self {
+        let now = Local::now();
+        let path = source.map_or(video.path.as_str(), |source| source.path.as_str());
+        let (filename, stem, ext) = if path.is_empty() {
+            // 稿件中已有的分P没有本地文件
+            (video.filename.clone(), video.title.clone(), String::new())
+        } else {
+            let path = Path::new(path);
+            let part = |s: Option<&std::ffi::OsStr>| {
+                s.map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            };
+            (
+                part(path.file_name()),
+                part(path.file_stem()),
+                part(path.extension()),
+            )
+        };
+        let mtime = match source {
+            Some(source) => Local.timestamp_millis_opt(source.mtime as i64).single(),
+            None => std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .map(DateTime::<Local>::from)
+                .ok(),
+        }
+        .unwrap_or(now);
+
+        Self {
+            filename,
+            stem,
+            ext,
+            mtime,
+            now,
+            index,
+            part_count,
+            duration: video.probe.as_ref().map(|probe| probe.duration),
+            account: account.to_string(),
+        }
+    }
+
+    fn variable(&self, name: &str, format: Option<&str>) -> Result<Option<String>> {
+        let value = match name {
+            "filename" => self.filename.clone(),
+            "stem" => self.stem.clone(),
+            "ext" => self.ext.clone(),
+            "index" => self.index.to_string(),
+            "part_count" => self.part_count.to_string(),
+            "account" => self.account.clone(),
+            "date" => format_time(&self.now, format.unwrap_or(DEFAULT_DATE_FORMAT))?,
+            "mtime" => format_time(&self.mtime, format.unwrap_or(DEFAULT_MTIME_FORMAT))?,
+            "duration" => self
+                .duration
+                .map(|secs| {
+                    let secs = secs.round() as u64;
+                    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+                })
+                .unwrap_or_default(),
+            _ => return Ok(None),
+        };
+        Ok(Some(value))
+    }
+}
+
+/// 按各字段渲染后的投稿信息
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplatePreview {
+    /// 加上前缀后的完整标题
+    pub title: String,
+    pub desc: String,
+    pub dynamic: String,
+    pub tag: String,
+    pub parts: Vec<String>,
+}
+
+impl TemplatePreview {
+    pub fn new(template: &TemplateConfig) -> Self {
+        Self {
+            title: format!("{}{}", template.title_prefix, template.title),
+            desc: template.desc.clone(),
+            dynamic: template.dynamic.clone(),
+            tag: template.tag.clone(),
+            parts: template.videos.iter().map(|v| v.title.clone()).collect(),
+        }
+    }
+}
+
+/// 渲染模板中的变量，标题、简介、动态和标签使用第一个分P的文件信息。
+/// sources 按任务ID索引各分P的源文件指纹
+pub fn apply(
+    template: &mut TemplateConfig,
+    account: &str,
+    sources: &HashMap<String, SourceFingerprint>,
+) -> Result<()> {
+    let part_count = template.videos.len();
+    let contexts: Vec<RenderContext> = template
+        .videos
+        .iter()
+        .enumerate()
+        .map(|(index, video)| {
+            let source = sources.get(&video.id);
+            RenderContext::for_video(video, source, index + 1, part_count, account)
+        })
+        .collect();
+    let first = contexts.first().cloned().unwrap_or_else(|| {
+        RenderContext::for_video(
+            &VideoInfo::from_path(String::new(), Path::new("")),
+            None,
+            1,
+            0,
+            account,
+        )
+    });
+
+    let field = |name: &str, text: &str, context: &RenderContext| {
+        render(text, context).map_err(|e| anyhow!("{name}模板错误: {e}"))
+    };
+    template.title_prefix = field("标题前缀", &template.title_prefix, &first)?;
+    template.title = field("标题", &template.title, &first)?;
+    template.desc = field("简介", &template.desc, &first)?;
+    template.dynamic = field("动态", &template.dynamic, &first)?;
+    template.tag = field("标签", &template.tag, &first)?;
+    // 设置了分P标题模板时覆盖各分P原有标题
+    let part_title = template.part_title.trim().to_string();
+    for (video, context) in template.videos.iter_mut().zip(&contexts) {
+        let source = if part_title.is_empty() {
+            video.title.clone()
+        } else {
+            part_title.clone()
+        };
+        video.title = field(&format!("P{}标题", context.index), &source, context)?;
+    }
+    Ok(())
+}
+
+/// 渲染一段文本中的变量
+pub fn render(text: &str, context: &RenderContext) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(['{', '}']) {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if let Some(after) = tail.strip_prefix('}') {
+            out.push('}');
+            rest = after;
+            continue;
+        }
+
+        let Some(end) = placeholder_end(tail) else {
+            out.push('{');
+            rest = &tail[1..];
+            continue;
+        };
+        match evaluate(&tail[1..end], context)? {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&tail[..=end]),
+        }
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// 找到与开头 `{` 对应的 `}`，引号中的字符不计入
+fn placeholder_end(tail: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in tail.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '}') => return Some(index),
+            (None, '{') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 计算 `变量[:格式]|过滤器|...`，变量不存在时返回 None
+fn evaluate(expr: &str, context: &RenderContext) -> Result<Option<String>> {
+    let mut segments = split_outside_quotes(expr, '|').into_iter();
+    let head = segments.next().unwrap_or_default();
+    let (name, format) = match head.split_once(':') {
+        Some((name, format)) => (name.trim(), Some(format)),
+        None => (head.trim(), None),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Ok(None);
+    }
+    let Some(mut value) = context.variable(name, format)? else {
+        return Ok(None);
+    };
+    for filter in segments {
+        value = apply_filter(filter.trim(), value)?;
+    }
+    Ok(Some(value))
+}
+
+fn apply_filter(filter: &str, value: String) -> Result<String> {
+    let (name, args) = match filter.split_once('(') {
+        Some((name, args)) => {
+            let args = args
+                .strip_suffix(')')
+                .ok_or_else(|| anyhow!("过滤器缺少右括号: {filter}"))?;
+            let args = split_outside_quotes(args, ',')
+                .into_iter()
+                .map(|arg| unquote(arg.trim()))
+                .collect();
+            (name.trim(), args)
+        }
+        None => (filter, Vec::new()),
+    };
+    let arg = |index: usize| {
+        args.get(index)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("过滤器 {name} 缺少参数"))
+    };
+
+    Ok(match name {
+        "upper" => value.to_uppercase(),
+        "lower" => value.to_lowercase(),
+        "trim" => value.trim().to_string(),
+        "truncate" => {
+            let max = arg(0)?
+                .parse::<usize>()
+                .map_err(|_| anyhow!("truncate 的参数应为数字: {}", args[0]))?;
+            value.chars().take(max).collect()
+        }
+        "replace" => {
+            let from = arg(0)?;
+            if from.is_empty() {
+                bail!("replace 的查找内容不能为空");
+            }
+            value.replace(from, args.get(1).map(String::as_str).unwrap_or(""))
+        }
+        "default" if value.is_empty() => arg(0)?.to_string(),
+        "default" => value,
+        _ => bail!("未知的过滤器: {name}"),
+    })
+}
+
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, c) if c == separator => {
+                parts.push(&text[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn unquote(arg: &str) -> String {
+    for q in ['"', '\''] {
+        if let Some(inner) = arg.strip_prefix(q).and_then(|a| a.strip_suffix(q)) {
+            return inner.to_string();
+        }
+    }
+    arg.to_string()
+}
+
+fn format_time(time: &DateTime<Local>, format: &str) -> Result<String> {
+    let mut out = String::new();
+    write!(out, "{}", time.format(format)).map_err(|_| anyhow!("无效的时间格式: {format}"))?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> RenderContext {
+        let video = VideoInfo::from_path("task-1".to_string(), Path::new(""));
+        let source = SourceFingerprint {
+            path: "/videos/Live Show.part.mp4".to_string(),
+            size: 1,
+            mtime: 0,
+            partial_hash: String::new(),
+        };
+        let mut context = RenderContext::for_video(&video, Some(&source), 2, 3, "uploader");
+        context.duration = Some(3725.4);
+        context
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        let context = context();
+        assert_eq!(
+            render("{stem} [{index}/{part_count}] {ext}", &context).unwrap(),
+            "Live Show.part [2/3] mp4"
+        );
+        assert_eq!(
+            render("{filename} by {account}", &context).unwrap(),
+            "Live Show.part.mp4 by uploader"
+        );
+        assert_eq!(render("{duration}", &context).unwrap(), "01:02:05");
+        assert_eq!(
+            render("{date:%Y}", &context).unwrap(),
+            context.now.format("%Y").to_string()
+        );
+    }
+
+    #[test]
+    fn keeps_braces_and_unknown_variables() {
+        let context = context();
+        assert_eq!(render("{{stem}}", &context).unwrap(), "{stem}");
+        assert_eq!(
+            render("{unknown} {a b}", &context).unwrap(),
+            "{unknown} {a b}"
+        );
+        assert_eq!(render("a } b {", &context).unwrap(), "a } b {");
+    }
+
+    #[test]
+    fn applies_filters() {
+        let context = context();
+        assert_eq!(render("{stem|upper}", &context).unwrap(), "LIVE SHOW.PART");
+        assert_eq!(
+            render("{stem|truncate(4)|lower}", &context).unwrap(),
+            "live"
+        );
+        assert_eq!(
+            render("{stem|replace('.part', '')}", &context).unwrap(),
+            "Live Show"
+        );
+        assert_eq!(
+            render("{stem|replace(\" \", \"_\")}", &context).unwrap(),
+            "Live_Show.part"
+        );
+        assert_eq!(
+            render("{ext|replace(mp4)|default(none)}", &context).unwrap(),
+            "none"
+        );
+        assert!(render("{stem|truncate(x)}", &context).is_err());
+        assert!(render("{stem|reverse}", &context).is_err());
+    }
+
+    #[test]
+    fn renders_from_source_after_path_cleared() {
+        let mut video = VideoInfo::from_path("task-1".to_string(), Path::new(""));
+        video.title = "P1".to_string();
+        let mut template = TemplateConfig {
+            title: "{stem}".to_string(),
+            videos: vec![video],
+            ..TemplateConfig::default()
+        };
+        let sources = HashMap::from([(
+            "task-1".to_string(),
+            SourceFingerprint {
+                path: "/videos/clip.mp4".to_string(),
+                size: 1,
+                mtime: 0,
+                partial_hash: String::new(),
+            },
+        )]);
+
+        apply(&mut template, "uploader", &sources).unwrap();
+        assert_eq!(template.title, "clip");
+    }
+}
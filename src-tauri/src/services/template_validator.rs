@@ -0,0 +1,352 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::models::TemplateConfig;
+
+/// 标题最多 80 个字符，分P标题相同
+const MAX_TITLE_CHARS: usize = 80;
+const MAX_TAGS: usize = 12;
+const MAX_TAG_CHARS: usize = 20;
+const MAX_DESC_CHARS: usize = 2000;
+/// 定时发布需在提交后 4 小时到 15 天之间
+const MIN_DTIME_SECS: i64 = 4 * 3600;
+const MAX_DTIME_SECS: i64 = 15 * 24 * 3600;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub enum Severity {
+    Error,   // 提交后会被B站拒绝
+    Warning, // 可以提交，但可能不是预期的结果
+}
+
+/// 某个字段的检查结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationIssue {
+    pub field: String,
+    pub severity: Severity,
+    pub message: String,
+    /// 是否可以由 fixed 中的自动修正解决
+    pub fixable: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationIssue>,
+    pub warnings: Vec<ValidationIssue>,
+    /// 应用了去除首尾空白、标签去重等安全修正后的模板
+    pub fixed: TemplateConfig,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// 有错误时返回包含全部错误的说明
+    pub fn ensure_valid(&self) -> Result<()> {
+        if self.is_valid() {
+            return Ok(());
+        }
+        let messages: Vec<String> = self
+            .errors
+            .iter()
+            .map(|issue| format!("{}: {}", issue.field, issue.message))
+            .collect();
+        Err(anyhow::anyhow!(
+            "投稿信息检查未通过: {}",
+            messages.join("; ")
+        ))
+    }
+
+    fn push(&mut self, field: &str, severity: Severity, message: String, fixable: bool) {
+        let issue = ValidationIssue {
+            field: field.to_string(),
+            severity,
+            message,
+            fixable,
+        };
+        match issue.severity {
+            Severity::Error => self.errors.push(issue),
+            Severity::Warning => self.warnings.push(issue),
+        }
+    }
+
+    fn error(&mut self, field: &str, message: String) {
+        self.push(field, Severity::Error, message, false);
+    }
+
+    fn fixable(&mut self, field: &str, message: String) {
+        self.push(field, Severity::Warning, message, true);
+    }
+}
+
+/// 按B站投稿接口的限制检查模板，规则在修正后的模板上运行
+pub fn validate(template: &TemplateConfig) -> ValidationReport {
+    let mut report = ValidationReport {
+        errors: Vec::new(),
+        warnings: Vec::new(),
+        fixed: template.clone(),
+    };
+    fix_whitespace(&mut report);
+    fix_tags(&mut report);
+
+    let fixed = report.fixed.clone();
+    check_title(&mut report, &fixed);
+    check_tags(&mut report, &fixed);
+    check_dtime(&mut report, &fixed);
+
+    if fixed.cover.is_empty() {
+        report.error("cover", "封面不能为空".to_string());
+    }
+    match fixed.copyright {
+        1 => {}
+        2 if fixed.source.is_empty() => {
+            report.error("source", "转载稿件需要填写转载来源".to_string())
+        }
+        2 => {}
+        other => report.error("copyright", format!("未知的类型: {other}，应为 1 或 2")),
+    }
+    if fixed.tid == 0 {
+        report.error("tid", "未选择投稿分区".to_string());
+    }
+    let desc_chars = fixed.desc.chars().count();
+    if desc_chars > MAX_DESC_CHARS {
+        report.error(
+            "desc",
+            format!("简介 {desc_chars} 字，超过 {MAX_DESC_CHARS} 字"),
+        );
+    }
+    if fixed.videos.is_empty() {
+        report.error("videos", "至少需要一个分P".to_string());
+    }
+    for (index, video) in fixed.videos.iter().enumerate() {
+        let chars = video.title.chars().count();
+        if chars > MAX_TITLE_CHARS {
+            report.error(
+                &format!("videos[{index}].title"),
+                format!("分P标题 {chars} 字，超过 {MAX_TITLE_CHARS} 字"),
+            );
+        }
+    }
+    report
+}
+
+fn fix_whitespace(report: &mut ValidationReport) {
+    let fixed = &mut report.fixed;
+    let mut trimmed = Vec::new();
+    for (field, value) in [
+        ("title", &mut fixed.title),
+        ("desc", &mut fixed.desc),
+        ("dynamic", &mut fixed.dynamic),
+        ("source", &mut fixed.source),
+        ("cover", &mut fixed.cover),
+    ] {
+        let trimmed_value = value.trim();
+        if trimmed_value.len() != value.len() {
+            *value = trimmed_value.to_string();
+            trimmed.push(field);
+        }
+    }
+    for video in fixed.videos.iter_mut() {
+        let trimmed_title = video.title.trim();
+        if trimmed_title.len() != video.title.len() {
+            video.title = trimmed_title.to_string();
+            trimmed.push("videos");
+        }
+    }
+    trimmed.dedup();
+    for field in trimmed {
+        report.fixable(field, "首尾有多余的空白".to_string());
+    }
+}
+
+/// 去掉标签的首尾空白、空标签和重复标签
+fn fix_tags(report: &mut ValidationReport) {
+    let mut tags: Vec<&str> = Vec::new();
+    let mut duplicated = false;
+    for tag in report.fixed.tag.split(',').map(str::trim) {
+        if tag.is_empty() {
+            continue;
+        }
+        if tags.contains(&tag) {
+            duplicated = true;
+            continue;
+        }
+        tags.push(tag);
+    }
+    let normalized = tags.join(",");
+    if normalized == report.fixed.tag {
+        return;
+    }
+    let message = if duplicated {
+        "存在重复的标签".to_string()
+    } else {
+        "标签中有多余的空白或逗号".to_string()
+    };
+    report.fixed.tag = normalized;
+    report.fixable("tag", message);
+}
+
+fn check_title(report: &mut ValidationReport, template: &TemplateConfig) {
+    // 与 into_bilibili_form 一样，标题前缀不为空时原样拼接在标题前
+    let title = if template.title_prefix.trim().is_empty() {
+        template.title.clone()
+    } else {
+        format!("{}{}", template.title_prefix, template.title)
+    };
+    let chars = title.chars().count();
+    if template.title.is_empty() {
+        report.error("title", "标题不能为空".to_string());
+    } else if chars > MAX_TITLE_CHARS {
+        report.error(
+            "title",
+            format!("标题 {chars} 字，超过 {MAX_TITLE_CHARS} 字"),
+        );
+    }
+}
+
+fn check_tags(report: &mut ValidationReport, template: &TemplateConfig) {
+    let tags: Vec<&str> = template.tag.split(',').filter(|t| !t.is_empty()).collect();
+    if tags.is_empty() {
+        report.error("tag", "至少需要一个标签".to_string());
+    } else if tags.len() > MAX_TAGS {
+        report.error("tag", format!("标签 {} 个，最多 {MAX_TAGS} 个", tags.len()));
+    }
+    for tag in tags {
+        if tag.chars().count() > MAX_TAG_CHARS {
+            report.error("tag", format!("标签「{tag}」超过 {MAX_TAG_CHARS} 字"));
+        }
+    }
+}
+
+fn check_dtime(report: &mut ValidationReport, template: &TemplateConfig) {
+    // 编辑已发布的稿件时 dtime 是原来的发布时间
+    let Some(dtime) = template
+        .dtime
+        .filter(|dtime| *dtime > 0 && template.aid.is_none())
+    else {
+        return;
+    };
+    let delta = dtime as i64 - chrono::Utc::now().timestamp();
+    if delta < MIN_DTIME_SECS {
+        report.error("dtime", "定时发布时间需在 4 小时之后".to_string());
+    } else if delta > MAX_DTIME_SECS {
+        report.error("dtime", "定时发布时间不能超过 15 天".to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::models::VideoInfo;
+
+    fn template() -> TemplateConfig {
+        let mut video = VideoInfo::from_path("task-1".to_string(), Path::new(""));
+        video.title = "P1".to_string();
+        TemplateConfig {
+            title: "标题".to_string(),
+            cover: "https://example.com/cover.jpg".to_string(),
+            tid: 17,
+            tag: "游戏,录播".to_string(),
+            videos: vec![video],
+            ..Default::default()
+        }
+    }
+
+    fn fields(issues: &[ValidationIssue]) -> Vec<&str> {
+        issues.iter().map(|issue| issue.field.as_str()).collect()
+    }
+
+    #[test]
+    fn accepts_valid_template() {
+        let report = validate(&template());
+        assert!(report.is_valid());
+        assert!(report.warnings.is_empty());
+        assert!(report.ensure_valid().is_ok());
+    }
+
+    #[test]
+    fn checks_title_with_prefix() {
+        let mut template = template();
+        template.title = "a".repeat(MAX_TITLE_CHARS);
+        assert!(validate(&template).is_valid());
+
+        // 空白前缀不会拼接到标题中
+        template.title_prefix = "  ".to_string();
+        assert!(validate(&template).is_valid());
+
+        template.title_prefix = "【录播】".to_string();
+        assert_eq!(fields(&validate(&template).errors), ["title"]);
+
+        template.title = String::new();
+        template.title_prefix = String::new();
+        assert_eq!(fields(&validate(&template).errors), ["title"]);
+    }
+
+    #[test]
+    fn checks_tags() {
+        let mut template = template();
+        template.tag = String::new();
+        assert_eq!(fields(&validate(&template).errors), ["tag"]);
+
+        template.tag = (0..=MAX_TAGS)
+            .map(|i| format!("t{i}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(fields(&validate(&template).errors), ["tag"]);
+
+        template.tag = format!("游戏,{}", "a".repeat(MAX_TAG_CHARS + 1));
+        assert_eq!(fields(&validate(&template).errors), ["tag"]);
+    }
+
+    #[test]
+    fn checks_dtime_window() {
+        let now = chrono::Utc::now().timestamp();
+        let mut template = template();
+
+        template.dtime = Some((now + 3600) as u32);
+        assert_eq!(fields(&validate(&template).errors), ["dtime"]);
+
+        template.dtime = Some((now + MIN_DTIME_SECS + 600) as u32);
+        assert!(validate(&template).is_valid());
+
+        template.dtime = Some((now + MAX_DTIME_SECS + 600) as u32);
+        assert_eq!(fields(&validate(&template).errors), ["dtime"]);
+
+        // 编辑已发布的稿件时不检查
+        template.dtime = Some((now - 3600) as u32);
+        template.aid = Some(1);
+        assert!(validate(&template).is_valid());
+    }
+
+    #[test]
+    fn fixes_whitespace_and_tags() {
+        let mut template = template();
+        template.title = "  标题 ".to_string();
+        template.videos[0].title = "P1 ".to_string();
+        template.tag = " 游戏, ,录播,游戏,".to_string();
+
+        let report = validate(&template);
+        assert!(report.is_valid());
+        assert_eq!(fields(&report.warnings), ["title", "videos", "tag"]);
+        assert!(report.warnings.iter().all(|issue| issue.fixable));
+        assert_eq!(report.fixed.title, "标题");
+        assert_eq!(report.fixed.videos[0].title, "P1");
+        assert_eq!(report.fixed.tag, "游戏,录播");
+    }
+
+    #[test]
+    fn reports_errors_together() {
+        let mut template = template();
+        template.cover = String::new();
+        template.tid = 0;
+        template.copyright = 2;
+        template.videos.clear();
+
+        let report = validate(&template);
+        assert_eq!(fields(&report.errors), ["cover", "source", "tid", "videos"]);
+        let message = report.ensure_valid().unwrap_err().to_string();
+        assert!(message.contains("cover") && message.contains("videos"));
+    }
+}
@@ -0,0 +1,574 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::{
+    models::{ConfigRoot, TranslationProvider, VideoInfo},
+    utils::get_translation_cache_json_path,
+};
+
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY_SECS: u64 = 2;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const DEEPL_DEFAULT_TARGET: &str = "ZH-HANS";
+
+/// 翻译接口，prompt 由接口自行决定是否使用
+#[async_trait]
+pub trait Translator: Send + Sync {
+    async fn translate(&self, text: &str, prompt: &str) -> Result<String>;
+}
+
+/// OpenAI 兼容的 chat/completions 接口
+struct OpenAiTranslator {
+    client: reqwest::Client,
+    endpoint: String,
+    api_key: String,
+    model: String,
+}
+
+#[async_trait]
+impl Translator for OpenAiTranslator {
+    async fn translate(&self, text: &str, prompt: &str) -> Result<String> {
+        let res: Value = self
+            .request(text, prompt)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let content = &res["choices"][0]["message"]["content"];
+        // 部分兼容接口把 content 拆成多段
+        let text = match content {
+            Value::Array(parts) => parts
+                .iter()
+                .filter_map(|part| part.as_str().or_else(|| part["text"].as_str()))
+                .collect(),
+            _ => content
+                .as_str()
+                .or_else(|| res["output_text"].as_str())
+                .unwrap_or_default()
+                .to_string(),
+        };
+        Ok(text)
+    }
+}
+
+impl OpenAiTranslator {
+    fn request(&self, text: &str, prompt: &str) -> reqwest::RequestBuilder {
+        let request = self.client.post(&self.endpoint).json(&json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": prompt },
+                { "role": "user", "content": text },
+            ],
+            "temperature": 0.2,
+        }));
+        if self.api_key.is_empty() {
+            request
+        } else {
+            request.bearer_auth(&self.api_key)
+        }
+    }
+}
+
+/// DeepL 不使用提示词，模型名称作为目标语言
+struct DeepLTranslator {
+    client: reqwest::Client,
+    endpoint: String,
+    api_key: String,
+    target_lang: String,
+}
+
+#[async_trait]
+impl Translator for DeepLTranslator {
+    async fn translate(&self, text: &str, _prompt: &str) -> Result<String> {
+        let res: Value = self
+            .request(text)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(res["translations"][0]["text"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+}
+
+impl DeepLTranslator {
+    fn request(&self, text: &str) -> reqwest::RequestBuilder {
+        self.client
+            .post(&self.endpoint)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("DeepL-Auth-Key {}", self.api_key),
+            )
+            .json(&json!({
+                "text": [text],
+                "target_lang": self.target_lang,
+            }))
+    }
+}
+
+/// Ollama 的 /api/chat 接口
+struct OllamaTranslator {
+    client: reqwest::Client,
+    endpoint: String,
+    model: String,
+}
+
+#[async_trait]
+impl Translator for OllamaTranslator {
+    async fn translate(&self, text: &str, prompt: &str) -> Result<String> {
+        let res: Value = self
+            .request(text, prompt)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(res["message"]["content"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+}
+
+impl OllamaTranslator {
+    fn request(&self, text: &str, prompt: &str) -> reqwest::RequestBuilder {
+        self.client.post(&self.endpoint).json(&json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": prompt },
+                { "role": "user", "content": text },
+            ],
+            "stream": false,
+            "options": { "temperature": 0.2 },
+        }))
+    }
+}
+
+/// 标题翻译，结果按接口类型、地址、模型、提示词和原文缓存到磁盘
+#[derive(Clone)]
+pub struct TranslationService {
+    client: reqwest::Client,
+    cache: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl Default for TranslationService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TranslationService {
+    pub fn new() -> Self {
+        let cache = match load_cache() {
+            Ok(cache) => cache,
+            Err(e) => {
+                warn!("读取翻译缓存失败: {}", e);
+                HashMap::new()
+            }
+        };
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            cache: Arc::new(Mutex::new(cache)),
+        }
+    }
+
+    /// 按当前配置翻译一段文本，失败时重试
+    pub async fn translate(&self, config: &ConfigRoot, text: &str) -> Result<String> {
+        self.translate_with_attempts(config, text, MAX_ATTEMPTS)
+            .await
+    }
+
+    async fn translate_with_attempts(
+        &self,
+        config: &ConfigRoot,
+        text: &str,
+        attempts: u32,
+    ) -> Result<String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(String::new());
+        }
+        let key = cache_key(config, text);
+        if let Some(cached) = self.cache.lock().await.get(&key) {
+            return Ok(cached.clone());
+        }
+
+        let translator = self.translator(config)?;
+        let translated = translate_with_retry(
+            translator.as_ref(),
+            text,
+            &config.translation_prompt,
+            attempts,
+            Duration::from_secs(RETRY_DELAY_SECS),
+        )
+        .await?;
+
+        let mut cache = self.cache.lock().await;
+        cache.insert(key, translated.clone());
+        if let Err(e) = save_cache(&cache) {
+            error!("保存翻译缓存失败: {}", e);
+        }
+        Ok(translated)
+    }
+
+    /// 开启分P标题翻译时翻译新任务的分P标题，只请求一次，失败时保留原标题
+    pub async fn auto_translate(&self, config: &ConfigRoot, video: &mut VideoInfo) {
+        if !config.translation_auto_parts || !is_configured(config) {
+            return;
+        }
+        match self.translate_with_attempts(config, &video.title, 1).await {
+            Ok(translated) => {
+                info!("自动翻译标题: {} -> {}", video.title, translated);
+                video.title = translated;
+            }
+            Err(e) => warn!("自动翻译失败，保留原标题: {} - {}", video.title, e),
+        }
+    }
+
+    fn translator(&self, config: &ConfigRoot) -> Result<Box<dyn Translator>> {
+        if !is_configured(config) {
+            anyhow::bail!("请先填写翻译 API 和模型名称");
+        }
+        let client = self.client.clone();
+        let api_url = config.translation_api_url.trim().trim_end_matches('/');
+        let api_key = config.translation_api_key.trim().to_string();
+        let model = config.translation_model.trim().to_string();
+        Ok(match config.translation_provider {
+            TranslationProvider::OpenAi => Box::new(OpenAiTranslator {
+                client,
+                endpoint: openai_endpoint(api_url).ok_or_else(|| anyhow!("翻译 API 地址无效"))?,
+                api_key,
+                model,
+            }),
+            TranslationProvider::DeepL => Box::new(DeepLTranslator {
+                client,
+                endpoint: deepl_endpoint(api_url, &api_key),
+                api_key,
+                target_lang: if model.is_empty() {
+                    DEEPL_DEFAULT_TARGET.to_string()
+                } else {
+                    model
+                },
+            }),
+            TranslationProvider::Ollama => Box::new(OllamaTranslator {
+                client,
+                endpoint: ollama_endpoint(api_url),
+                model,
+            }),
+        })
+    }
+}
+
+/// 翻译失败或结果为空时重试，第 n 次重试前等待 n 倍的 delay
+async fn translate_with_retry(
+    translator: &dyn Translator,
+    text: &str,
+    prompt: &str,
+    attempts: u32,
+    delay: Duration,
+) -> Result<String> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let result = translator
+            .translate(text, prompt)
+            .await
+            .map(|translated| clean(&translated));
+        match result {
+            Ok(translated) if !translated.is_empty() => return Ok(translated),
+            Ok(_) if attempt >= attempts => anyhow::bail!("翻译结果为空"),
+            Err(e) if attempt >= attempts => return Err(e),
+            Ok(_) => warn!("翻译结果为空，重试第 {} 次", attempt),
+            Err(e) => warn!("翻译失败，重试第 {} 次: {}", attempt, e),
+        }
+        tokio::time::sleep(delay * attempt).await;
+    }
+}
+
+fn is_configured(config: &ConfigRoot) -> bool {
+    match config.translation_provider {
+        TranslationProvider::OpenAi => {
+            !config.translation_api_url.trim().is_empty()
+                && !config.translation_model.trim().is_empty()
+        }
+        TranslationProvider::DeepL => !config.translation_api_key.trim().is_empty(),
+        TranslationProvider::Ollama => !config.translation_model.trim().is_empty(),
+    }
+}
+
+/// 与前端原有逻辑相同，补全到 /v1/chat/completions
+fn openai_endpoint(api_url: &str) -> Option<String> {
+    if api_url.is_empty() {
+        return None;
+    }
+    let lower = api_url.to_ascii_lowercase();
+    Some(if lower.ends_with("/chat/completions") {
+        api_url.to_string()
+    } else if lower.ends_with("/v1") {
+        format!("{api_url}/chat/completions")
+    } else {
+        format!("{api_url}/v1/chat/completions")
+    })
+}
+
+/// 未填写地址时按密钥类型选择免费版或专业版接口
+fn deepl_endpoint(api_url: &str, api_key: &str) -> String {
+    if !api_url.is_empty() {
+        return api_url.to_string();
+    }
+    if api_key.ends_with(":fx") {
+        "https://api-free.deepl.com/v2/translate".to_string()
+    } else {
+        "https://api.deepl.com/v2/translate".to_string()
+    }
+}
+
+fn ollama_endpoint(api_url: &str) -> String {
+    if api_url.is_empty() {
+        "http://127.0.0.1:11434/api/chat".to_string()
+    } else if api_url.ends_with("/api/chat") {
+        api_url.to_string()
+    } else {
+        format!("{api_url}/api/chat")
+    }
+}
+
+/// 去掉换行和模型常加的首尾引号
+fn clean(text: &str) -> String {
+    text.replace("\r\n", " ")
+        .replace('\n', " ")
+        .trim()
+        .trim_matches(['"', '\'', '“', '”'])
+        .trim()
+        .to_string()
+}
+
+fn cache_key(config: &ConfigRoot, text: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        format!("{:?}", config.translation_provider).as_str(),
+        config.translation_api_url.trim().trim_end_matches('/'),
+        config.translation_model.trim(),
+        config.translation_prompt.as_str(),
+        text,
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn load_cache() -> Result<HashMap<String, String>> {
+    let path = get_translation_cache_json_path()?;
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = std::fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn save_cache(cache: &HashMap<String, String>) -> Result<()> {
+    let content = serde_json::to_string(cache)?;
+    let path = get_translation_cache_json_path()?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, content)?;
+    std::fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn config() -> ConfigRoot {
+        ConfigRoot {
+            translation_api_url: "https://api.example.com/v1".to_string(),
+            translation_model: "gpt-4o-mini".to_string(),
+            ..ConfigRoot::default()
+        }
+    }
+
+    fn build(request: reqwest::RequestBuilder) -> (reqwest::Request, Value) {
+        let request = request.build().unwrap();
+        let body = serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        (request, body)
+    }
+
+    /// 按顺序返回预设结果并记录调用次数
+    struct Scripted {
+        results: Vec<Result<&'static str, &'static str>>,
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl Translator for Scripted {
+        async fn translate(&self, _text: &str, _prompt: &str) -> Result<String> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) as usize;
+            match self.results[call.min(self.results.len() - 1)] {
+                Ok(text) => Ok(text.to_string()),
+                Err(e) => Err(anyhow!(e)),
+            }
+        }
+    }
+
+    fn scripted(results: Vec<Result<&'static str, &'static str>>) -> Scripted {
+        Scripted {
+            results,
+            calls: AtomicU32::new(0),
+        }
+    }
+
+    #[test]
+    fn cache_key_covers_every_setting() {
+        let base = config();
+        let key = cache_key(&base, "hello");
+        assert_eq!(key, cache_key(&base, "hello"));
+        assert_ne!(key, cache_key(&base, "world"));
+
+        let mut changed = base.clone();
+        changed.translation_api_url = "https://other.example.com/v1".to_string();
+        assert_ne!(key, cache_key(&changed, "hello"));
+
+        changed = base.clone();
+        changed.translation_api_url = "https://api.example.com/v1/ ".to_string();
+        assert_eq!(key, cache_key(&changed, "hello"));
+
+        changed = base.clone();
+        changed.translation_model = "other".to_string();
+        assert_ne!(key, cache_key(&changed, "hello"));
+
+        changed = base.clone();
+        changed.translation_prompt = "other".to_string();
+        assert_ne!(key, cache_key(&changed, "hello"));
+
+        changed = base.clone();
+        changed.translation_provider = TranslationProvider::Ollama;
+        assert_ne!(key, cache_key(&changed, "hello"));
+    }
+
+    #[test]
+    fn completes_endpoints() {
+        assert_eq!(openai_endpoint(""), None);
+        assert_eq!(
+            openai_endpoint("https://api.example.com").as_deref(),
+            Some("https://api.example.com/v1/chat/completions")
+        );
+        assert_eq!(
+            openai_endpoint("https://api.example.com/V1").as_deref(),
+            Some("https://api.example.com/V1/chat/completions")
+        );
+        assert_eq!(
+            openai_endpoint("https://api.example.com/v1/chat/completions").as_deref(),
+            Some("https://api.example.com/v1/chat/completions")
+        );
+        assert_eq!(
+            deepl_endpoint("", "key:fx"),
+            "https://api-free.deepl.com/v2/translate"
+        );
+        assert_eq!(
+            deepl_endpoint("", "key"),
+            "https://api.deepl.com/v2/translate"
+        );
+        assert_eq!(
+            deepl_endpoint("https://deepl.example.com", "key:fx"),
+            "https://deepl.example.com"
+        );
+        assert_eq!(ollama_endpoint(""), "http://127.0.0.1:11434/api/chat");
+        assert_eq!(
+            ollama_endpoint("http://gpu:11434"),
+            "http://gpu:11434/api/chat"
+        );
+    }
+
+    #[test]
+    fn builds_openai_request() {
+        let translator = OpenAiTranslator {
+            client: reqwest::Client::new(),
+            endpoint: "https://api.example.com/v1/chat/completions".to_string(),
+            api_key: "sk-test".to_string(),
+            model: "gpt-4o-mini".to_string(),
+        };
+        let (request, body) = build(translator.request("hello", "prompt"));
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.example.com/v1/chat/completions"
+        );
+        assert_eq!(request.headers()["authorization"], "Bearer sk-test");
+        assert_eq!(body["model"], "gpt-4o-mini");
+        assert_eq!(body["messages"][0]["content"], "prompt");
+        assert_eq!(body["messages"][1]["content"], "hello");
+
+        let anonymous = OpenAiTranslator {
+            api_key: String::new(),
+            ..translator
+        };
+        let (request, _) = build(anonymous.request("hello", "prompt"));
+        assert!(request.headers().get("authorization").is_none());
+    }
+
+    #[test]
+    fn builds_deepl_request() {
+        let translator = DeepLTranslator {
+            client: reqwest::Client::new(),
+            endpoint: "https://api-free.deepl.com/v2/translate".to_string(),
+            api_key: "key:fx".to_string(),
+            target_lang: DEEPL_DEFAULT_TARGET.to_string(),
+        };
+        let (request, body) = build(translator.request("hello"));
+        assert_eq!(request.headers()["authorization"], "DeepL-Auth-Key key:fx");
+        assert_eq!(body["text"], json!(["hello"]));
+        assert_eq!(body["target_lang"], "ZH-HANS");
+    }
+
+    #[test]
+    fn builds_ollama_request() {
+        let translator = OllamaTranslator {
+            client: reqwest::Client::new(),
+            endpoint: "http://127.0.0.1:11434/api/chat".to_string(),
+            model: "qwen2.5".to_string(),
+        };
+        let (request, body) = build(translator.request("hello", "prompt"));
+        assert_eq!(request.url().as_str(), "http://127.0.0.1:11434/api/chat");
+        assert_eq!(body["model"], "qwen2.5");
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["messages"][1]["content"], "hello");
+    }
+
+    #[tokio::test]
+    async fn retries_until_non_empty_result() {
+        let translator = scripted(vec![Err("timeout"), Ok(" \"\" "), Ok("“你好”\n")]);
+        let translated =
+            translate_with_retry(&translator, "hello", "prompt", 3, Duration::ZERO).await;
+        assert_eq!(translated.unwrap(), "你好");
+        assert_eq!(translator.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn stops_after_last_attempt() {
+        let translator = scripted(vec![Err("timeout")]);
+        let result = translate_with_retry(&translator, "hello", "prompt", 3, Duration::ZERO).await;
+        assert_eq!(result.unwrap_err().to_string(), "timeout");
+        assert_eq!(translator.calls.load(Ordering::SeqCst), 3);
+
+        let translator = scripted(vec![Ok("")]);
+        let result = translate_with_retry(&translator, "hello", "prompt", 1, Duration::ZERO).await;
+        assert!(result.is_err());
+        assert_eq!(translator.calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::broadcast;
+use tracing::{trace, warn};
+
+use crate::models::{Draft, PauseReason, TaskStatus, UploadTask};
+
+/// 同一任务两次进度事件之间的最小间隔
+const PROGRESS_THROTTLE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressPayload {
+    pub task_id: String,
+    pub progress: f64,
+    pub total_transmit_bytes: u64,
+    pub speed_bytes_per_sec: f64,
+    pub eta_secs: Option<u64>,
+    pub average_speed: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusPayload {
+    pub task_id: String,
+    pub title: String,
+    pub uid: u64,
+    pub template: String,
+    pub status: TaskStatus,
+    pub error_message: Option<String>,
+    pub pause_reason: Option<PauseReason>,
+}
+
+/// 投稿或编辑稿件的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct SubmitPayload {
+    pub uid: u64,
+    pub template: String,
+    pub success: bool,
+    pub aid: Option<u64>,
+    pub bvid: Option<String>,
+    pub error_message: Option<String>,
+}
+
+/// 文件夹监控发现的文件未能加入上传队列，例如未通过视频检查
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchRejectedPayload {
+    pub path: String,
+    pub uid: u64,
+    pub template: String,
+    pub reason: String,
+}
+
+/// 上传服务推送给前端的事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "payload", rename_all = "kebab-case")]
+pub enum UploadEvent {
+    TaskCreated(UploadTask),
+    Progress(ProgressPayload),
+    StatusChanged(StatusPayload),
+    Completed(UploadTask),
+    Failed(StatusPayload),
+    TaskRemoved(String),
+    TaskUpdated(UploadTask),
+    QueueReordered(Vec<String>),
+    DraftUpdated(Draft),
+    SubmitResult(SubmitPayload),
+    WatchRejected(WatchRejectedPayload),
+}
+
+impl UploadEvent {
+    /// 前端监听的事件名
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TaskCreated(_) => "upload://task-created",
+            Self::Progress(_) => "upload://progress",
+            Self::StatusChanged(_) => "upload://status-changed",
+            Self::Completed(_) => "upload://completed",
+            Self::Failed(_) => "upload://failed",
+            Self::TaskRemoved(_) => "upload://task-removed",
+            Self::TaskUpdated(_) => "upload://task-updated",
+            Self::QueueReordered(_) => "upload://queue-reordered",
+            Self::DraftUpdated(_) => "upload://draft-updated",
+            Self::SubmitResult(_) => "upload://submit-result",
+            Self::WatchRejected(_) => "upload://watch-rejected",
+        }
+    }
+}
+
+/// 上传事件分发，发送到前端窗口并广播给后端订阅者
+#[derive(Clone)]
+pub struct UploadEvents {
+    app: Arc<RwLock<Option<AppHandle>>>,
+    tx: broadcast::Sender<UploadEvent>,
+    last_progress: Arc<RwLock<HashMap<String, Instant>>>,
+}
+
+impl Default for UploadEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UploadEvents {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(256);
+        Self {
+            app: Arc::new(RwLock::new(None)),
+            tx,
+            last_progress: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn set_app_handle(&self, app: AppHandle) {
+        if let Ok(mut guard) = self.app.write() {
+            *guard = Some(app);
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<UploadEvent> {
+        self.tx.subscribe()
+    }
+
+    pub fn emit(&self, event: UploadEvent) {
+        trace!("上传事件: {}", event.name());
+        if let Ok(guard) = self.app.read()
+            && let Some(app) = guard.as_ref()
+            && let Err(e) = app.emit(event.name(), &event)
+        {
+            warn!("发送上传事件失败: {}", e);
+        }
+        // 没有订阅者时发送会失败，忽略即可
+        let _ = self.tx.send(event);
+    }
+
+    pub fn task_created(&self, task: &UploadTask) {
+        self.emit(UploadEvent::TaskCreated(task.clone()));
+    }
+
+    pub fn task_removed(&self, task_id: &str) {
+        if let Ok(mut last) = self.last_progress.write() {
+            last.remove(task_id);
+        }
+        self.emit(UploadEvent::TaskRemoved(task_id.to_string()));
+    }
+
+    pub fn task_updated(&self, task: &UploadTask) {
+        self.emit(UploadEvent::TaskUpdated(task.clone()));
+    }
+
+    /// 推送调整后的队列顺序
+    pub fn queue_reordered(&self, order: Vec<String>) {
+        self.emit(UploadEvent::QueueReordered(order));
+    }
+
+    pub fn draft_updated(&self, draft: &Draft) {
+        self.emit(UploadEvent::DraftUpdated(draft.clone()));
+    }
+
+    /// 推送投稿结果，成功时 result 为B站返回的 data
+    pub fn submit_result(&self, uid: u64, template: &str, result: &Result<Value, String>) {
+        let payload = match result {
+            Ok(data) => SubmitPayload {
+                uid,
+                template: template.to_string(),
+                success: true,
+                aid: data["aid"].as_u64(),
+                bvid: data["bvid"].as_str().map(String::from),
+                error_message: None,
+            },
+            Err(e) => SubmitPayload {
+                uid,
+                template: template.to_string(),
+                success: false,
+                aid: None,
+                bvid: None,
+                error_message: Some(e.clone()),
+            },
+        };
+        self.emit(UploadEvent::SubmitResult(payload));
+    }
+
+    pub fn watch_rejected(&self, path: &str, uid: u64, template: &str, reason: String) {
+        self.emit(UploadEvent::WatchRejected(WatchRejectedPayload {
+            path: path.to_string(),
+            uid,
+            template: template.to_string(),
+            reason,
+        }));
+    }
+
+    /// 推送任务状态，完成和失败时额外推送对应事件
+    pub fn status_changed(&self, task: &UploadTask) {
+        let payload = StatusPayload {
+            task_id: task.id.clone(),
+            title: task.video.title.clone(),
+            uid: task.user.uid,
+            template: task.template.clone(),
+            status: task.status.clone(),
+            error_message: task.error_message.clone(),
+            pause_reason: task.pause_reason,
+        };
+        self.emit(UploadEvent::StatusChanged(payload.clone()));
+
+        if task.is_completed() {
+            self.emit(UploadEvent::Completed(task.clone()));
+        } else if task.is_failed() {
+            self.emit(UploadEvent::Failed(payload));
+        }
+    }
+
+    /// 推送上传进度，同一任务的进度事件会被节流
+    pub fn progress(&self, task: &UploadTask) {
+        let now = Instant::now();
+        if let Ok(mut last) = self.last_progress.write() {
+            if last
+                .get(&task.id)
+                .is_some_and(|at| now.duration_since(*at) < PROGRESS_THROTTLE)
+            {
+                return;
+            }
+            last.insert(task.id.clone(), now);
+        }
+
+        self.emit(UploadEvent::Progress(ProgressPayload {
+            task_id: task.id.clone(),
+            progress: task.progress,
+            total_transmit_bytes: task.total_transmit_bytes,
+            speed_bytes_per_sec: task.speed_bytes_per_sec,
+            eta_secs: task.eta_secs,
+            average_speed: task.average_speed,
+        }));
+    }
+}
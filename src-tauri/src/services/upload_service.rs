@@ -1,23 +1,40 @@
-use std::{collections::HashMap, path::PathBuf, pin::Pin, sync::Arc, task::Poll};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Poll, ready},
+};
 
 use crate::{
     MyClient,
-    models::{ConfigRoot, UploadTask, User, VideoInfo},
-};
-use anyhow::Result;
-use biliup::{
-    client::StatelessClient,
-    uploader::{VideoFile, line},
+    models::{
+        ConfigRoot, Draft, ErrorKind, QueueStats, SourceFingerprint, UploadGroup, UploadTask, User,
+        VideoInfo,
+    },
+    services::{
+        rate_limiter::{BandwidthLimits, RateLimiter},
+        submit_pipeline::SubmitPipeline,
+        upload_events::UploadEvents,
+        upos::{
+            self, LineBenchmark, LoginRequired, SessionRejected, UploadSession, UploadedPart,
+            UploadedVideo,
+        },
+    },
+    utils::{get_upload_queue_json_path, get_upload_sessions_json_path},
 };
+use anyhow::{Context, Result};
 use bytes::{Buf, Bytes};
 use futures::Stream;
 use futures::StreamExt;
 use indexmap::IndexMap;
 use reqwest::Body;
-use tauri::async_runtime::block_on;
+use serde::Serialize;
 use tokio::{
+    io::{AsyncReadExt, AsyncSeekExt},
     select,
-    sync::{Mutex, mpsc},
+    sync::{Mutex, mpsc, watch},
     task,
 };
 use tracing::{debug, error, info, trace, warn};
@@ -29,14 +46,88 @@ macro_rules! task_title {
     };
 }
 
+pub(crate) type UploadQueue = Arc<Mutex<IndexMap<String, Arc<Mutex<UploadTask>>>>>;
+
+/// 队列持久化的间隔（秒）
+const PERSIST_INTERVAL_SECS: u64 = 5;
+
+/// 暂停后等待已开始的分片完成的最长时间
+const PAUSE_GRACE: std::time::Duration = std::time::Duration::from_secs(15);
+
 pub struct UploadService {
-    upload_queue: Arc<Mutex<IndexMap<String, Arc<Mutex<UploadTask>>>>>,
+    upload_queue: UploadQueue,
     upload_handle: Arc<Mutex<HashMap<String, task::JoinHandle<()>>>>,
     _upload_backgnd: task::JoinHandle<()>,
     max_running: Arc<Mutex<u32>>,
+    saved_queue: Arc<Mutex<SavedQueue>>,
+    events: UploadEvents,
+    bandwidth: Arc<BandwidthLimits>,
+    line_benchmarks: LineBenchmarks,
+    pipeline: SubmitPipeline,
     stop_tx: mpsc::Sender<()>,
 }
 
+/// 各账号的线路测速结果
+pub type LineBenchmarks = Arc<Mutex<HashMap<u64, AccountBenchmark>>>;
+
+/// 线路测速结果的有效期（毫秒），过期后回到默认的线路选择
+const BENCHMARK_TTL_MS: usize = 6 * 60 * 60 * 1000;
+
+/// 单个账号在当前代理下的线路测速结果，已按速度排序
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountBenchmark {
+    pub uid: u64,
+    pub proxy: Option<String>,
+    pub tested_at: usize,
+    pub lines: Vec<LineBenchmark>,
+}
+
+impl AccountBenchmark {
+    fn is_fresh(&self, proxy: Option<&str>) -> bool {
+        let now = chrono::Utc::now().timestamp_millis() as usize;
+        self.proxy.as_deref() == proxy && now.saturating_sub(self.tested_at) < BENCHMARK_TTL_MS
+    }
+
+    /// 测速成功的线路，按排名先后
+    fn ranked_lines(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .filter(|line| line.error.is_none())
+            .map(|line| line.line.clone())
+            .collect()
+    }
+}
+
+/// 对每个账号依次测速，结果写入缓存
+pub async fn benchmark_accounts(
+    cache: &LineBenchmarks,
+    accounts: Vec<(u64, Option<String>)>,
+) -> Vec<AccountBenchmark> {
+    let mut results = Vec::with_capacity(accounts.len());
+    for (uid, proxy) in accounts {
+        info!("开始线路测速: {} (代理: {:?})", uid, proxy);
+        let benchmark = AccountBenchmark {
+            uid,
+            lines: upos::benchmark(proxy.as_deref()).await,
+            proxy,
+            tested_at: chrono::Utc::now().timestamp_millis() as usize,
+        };
+        cache.lock().await.insert(uid, benchmark.clone());
+        results.push(benchmark);
+    }
+    results
+}
+
+/// 后台调度和上传任务共享的状态
+#[derive(Clone)]
+struct UploadContext {
+    events: UploadEvents,
+    bandwidth: Arc<BandwidthLimits>,
+    config: Arc<Mutex<ConfigRoot>>,
+    line_benchmarks: LineBenchmarks,
+    pipeline: SubmitPipeline,
+}
+
 impl UploadService {
     pub async fn set_max_concurrent(&self, max_curr: u32) {
         let mut curr = self.max_running.lock().await;
@@ -46,7 +137,7 @@ impl UploadService {
         debug!("更新最大并发上传数: {} -> {}", old, max_curr);
     }
 
-    pub fn new(max_curr: u32) -> Self {
+    pub fn new(max_curr: u32, config: Arc<Mutex<ConfigRoot>>) -> Self {
         let max_running = Arc::new(Mutex::new(max_curr));
         let max_running_clone = Arc::clone(&max_running);
 
@@ -56,6 +147,21 @@ impl UploadService {
         let upload_handle = Arc::new(Mutex::new(HashMap::new()));
         let upload_handle_clone = Arc::clone(&upload_handle);
 
+        let saved_queue = Arc::new(Mutex::new(SavedQueue::default()));
+        let saved_queue_clone = Arc::clone(&saved_queue);
+
+        let events = UploadEvents::new();
+        let bandwidth = Arc::new(BandwidthLimits::default());
+        let line_benchmarks = Arc::new(Mutex::new(HashMap::new()));
+        let pipeline = SubmitPipeline::default();
+        let context = UploadContext {
+            events: events.clone(),
+            bandwidth: Arc::clone(&bandwidth),
+            config,
+            line_benchmarks: Arc::clone(&line_benchmarks),
+            pipeline: pipeline.clone(),
+        };
+
         let (stop_tx, stop_rx) = mpsc::channel(1);
 
         Self {
@@ -66,97 +172,334 @@ impl UploadService {
                     upload_queue_clone,
                     upload_handle_clone,
                     max_running_clone,
+                    saved_queue_clone,
+                    context,
                     stop_rx,
                 )
                 .await;
             }),
             max_running,
+            saved_queue,
+            events,
+            bandwidth,
+            line_benchmarks,
+            pipeline,
             stop_tx,
         }
     }
 
-    /// 创建上传任务
+    /// 绑定前端窗口，之后的上传事件会推送到前端
+    pub fn set_app_handle(&self, app: tauri::AppHandle) {
+        self.events.set_app_handle(app);
+    }
+
+    pub fn events(&self) -> &UploadEvents {
+        &self.events
+    }
+
+    /// 带宽限制，修改后对正在上传的任务立即生效
+    pub fn bandwidth(&self) -> &BandwidthLimits {
+        &self.bandwidth
+    }
+
+    /// 上传完成后自动投稿的草稿
+    pub fn submit_pipeline(&self) -> &SubmitPipeline {
+        &self.pipeline
+    }
+
+    /// 登记一次投稿，任务全部上传完成后由后台自动投稿
+    pub async fn create_draft(
+        &self,
+        uid: u64,
+        template: &str,
+        task_ids: Vec<String>,
+    ) -> Result<Draft> {
+        if task_ids.is_empty() {
+            return Err(anyhow::anyhow!("投稿至少需要一个分P"));
+        }
+        {
+            let queue = self.upload_queue.lock().await;
+            for task_id in &task_ids {
+                let task_mutex = queue
+                    .get(task_id)
+                    .ok_or_else(|| anyhow::anyhow!("任务ID不存在: {}", task_id))?;
+                let task = task_mutex.lock().await;
+                if task.user.uid != uid || task.template != template {
+                    return Err(anyhow::anyhow!(
+                        "任务不属于该模板: {} ({})",
+                        task.title(),
+                        task.template
+                    ));
+                }
+            }
+        }
+
+        let draft = Draft::new(uid, template, task_ids);
+        self.pipeline.add(draft.clone(), &self.events).await;
+        Ok(draft)
+    }
+
+    /// 登记向已发布稿件追加分P，任务全部上传完成后合并分P列表并编辑稿件
+    pub async fn append_parts(&self, uid: u64, aid: u64, task_ids: Vec<String>) -> Result<Draft> {
+        if task_ids.is_empty() {
+            return Err(anyhow::anyhow!("追加分P至少需要一个任务"));
+        }
+        {
+            let queue = self.upload_queue.lock().await;
+            for task_id in &task_ids {
+                let task_mutex = queue
+                    .get(task_id)
+                    .ok_or_else(|| anyhow::anyhow!("任务ID不存在: {}", task_id))?;
+                let task = task_mutex.lock().await;
+                if task.user.uid != uid {
+                    return Err(anyhow::anyhow!("任务不属于该账号: {}", task.title()));
+                }
+            }
+        }
+
+        let draft = Draft::append(uid, aid, task_ids);
+        self.pipeline.add(draft.clone(), &self.events).await;
+        Ok(draft)
+    }
+
+    /// 线路测速结果的缓存，自动选择线路和重试换线时使用
+    pub fn line_benchmarks(&self) -> LineBenchmarks {
+        Arc::clone(&self.line_benchmarks)
+    }
+
+    /// 从磁盘恢复上次退出时的上传队列
+    pub async fn restore_queue(
+        &self,
+        config: Arc<Mutex<ConfigRoot>>,
+        clients: Arc<Mutex<HashMap<u64, MyClient>>>,
+    ) -> Result<usize> {
+        let path = get_upload_queue_json_path()?;
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        let auto_start = config.lock().await.auto_start;
+        let auths = match load_session_auths() {
+            Ok(auths) => auths,
+            Err(e) => {
+                warn!("读取上传会话凭证失败，未完成的任务将重新上传: {}", e);
+                HashMap::new()
+            }
+        };
+        let tasks = restore_tasks(&content, auths, auto_start)?;
+
+        let mut queue = self.upload_queue.lock().await;
+        let mut restored = 0;
+        for mut task in tasks {
+            if queue.contains_key(&task.id) {
+                continue;
+            }
+            task.attach(Arc::clone(&config), Arc::clone(&clients));
+            debug!("恢复上传任务: {} ({:?})", task.title(), task.status);
+            queue.insert(task.id.clone(), Arc::new(Mutex::new(task)));
+            restored += 1;
+        }
+        drop(queue);
+
+        info!("已恢复 {} 个上传任务", restored);
+        Ok(restored)
+    }
+
+    /// 不再把上传队列写入磁盘
+    pub async fn disable_persistence(&self) {
+        self.saved_queue.lock().await.disabled = true;
+    }
+
+    /// 立即将上传队列写入磁盘
+    pub async fn save_queue(&self) {
+        if let Err(e) = persist_queue(&self.upload_queue, &self.saved_queue).await {
+            error!("保存上传队列失败: {}", e);
+        }
+    }
+
+    /// 创建上传任务，source 需在持有应用锁之前用 `SourceFingerprint::read_async` 读取
     pub async fn create_task(
         &mut self,
         user: &User,
         template: &str,
         video: &VideoInfo,
+        source: Option<SourceFingerprint>,
         config_copy: Arc<Mutex<ConfigRoot>>,
         clients: Arc<Mutex<HashMap<u64, MyClient>>>,
     ) -> Result<bool> {
-        let task = UploadTask::new(user, template, video, config_copy, clients);
+        let task = UploadTask::new(user, template, video, source, config_copy, clients);
         if self.upload_queue.lock().await.contains_key(&task.id) {
             warn!("任务已存在: {:?}", task);
             return Ok(false); // 任务已存在
         }
         // 将任务添加到上传队列
         let title = task.title().clone();
+        self.events.task_created(&task);
         self.upload_queue
             .lock()
             .await
             .insert(task.id.clone(), Arc::new(Mutex::new(task)));
         info!("创建上传任务: {:?}", title);
+        self.save_queue().await;
         Ok(true)
     }
 
+    /// 获取完整的上传队列，前端通过事件更新后仅在需要重新同步时调用
     pub async fn get_upload_queue(&mut self) -> Result<Vec<UploadTask>> {
         let mut tasks = Vec::new();
         // trace!("获取上传队列");
-        for task_mutex in self.upload_queue.lock().await.values() {
-            tasks.push(task_mutex.lock().await.clone());
+        let task_mutexes: Vec<_> = self.upload_queue.lock().await.values().cloned().collect();
+        for task_mutex in task_mutexes {
+            let task = {
+                let mut task = task_mutex.lock().await;
+                task.refresh_speed();
+                task.clone()
+            };
 
-            if task_mutex.lock().await.is_completed() || task_mutex.lock().await.is_failed() {
-                let title = task_title!(task_mutex);
+            if task.is_completed() || task.is_failed() {
                 // pull out from the handles
-                if self
-                    .upload_handle
-                    .lock()
-                    .await
-                    .remove(&task_mutex.lock().await.id)
-                    .is_some()
-                {
-                    info!("清理后台任务: {}", title)
+                if self.upload_handle.lock().await.remove(&task.id).is_some() {
+                    info!("清理后台任务: {}", task.title())
                 }
             }
+            tasks.push(task);
         }
         Ok(tasks)
     }
 
-    /// 上传视频
-    pub async fn start_upload(&mut self, task_id: &str) -> Result<bool> {
-        info!("尝试开始任务: {}", task_id);
-        if let Some(task_mutex) = self.upload_queue.lock().await.get(task_id) {
-            if task_mutex.lock().await.is_running() || task_mutex.lock().await.is_completed() {
+    /// 将任务移动到队列中的指定位置，超出范围时移动到末尾
+    pub async fn move_task(&self, task_id: &str, index: usize) -> Result<bool> {
+        let order = {
+            let mut queue = self.upload_queue.lock().await;
+            let from = queue
+                .get_index_of(task_id)
+                .ok_or_else(|| anyhow::anyhow!("任务ID不存在: {}", task_id))?;
+            let to = index.min(queue.len() - 1);
+            if from == to {
                 return Ok(false);
             }
-            if task_mutex.lock().await.is_paused()
-                || task_mutex.lock().await.is_failed()
-                || task_mutex.lock().await.is_waiting()
+            queue.move_index(from, to);
+            debug!("移动任务: {} {} -> {}", task_id, from, to);
+            queue.keys().cloned().collect::<Vec<_>>()
+        };
+
+        self.events.queue_reordered(order);
+        self.save_queue().await;
+        Ok(true)
+    }
+
+    pub async fn move_task_to_top(&self, task_id: &str) -> Result<bool> {
+        self.move_task(task_id, 0).await
+    }
+
+    pub async fn move_task_to_bottom(&self, task_id: &str) -> Result<bool> {
+        self.move_task(task_id, usize::MAX).await
+    }
+
+    /// 设置任务优先级，数值越大越先开始
+    pub async fn set_task_priority(&self, task_id: &str, priority: i32) -> Result<bool> {
+        let task_mutex = self
+            .upload_queue
+            .lock()
+            .await
+            .get(task_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("任务ID不存在: {}", task_id))?;
+
+        {
+            let mut task = task_mutex.lock().await;
+            task.priority = priority;
+            info!("任务优先级: {} -> {}", task.title(), priority);
+            self.events.task_updated(&task);
+        }
+        self.save_queue().await;
+        Ok(true)
+    }
+
+    /// 汇总整个队列的进度、速率和剩余时间
+    pub async fn get_queue_stats(&self) -> QueueStats {
+        let task_mutexes: Vec<_> = self.upload_queue.lock().await.values().cloned().collect();
+        let mut stats = QueueStats::default();
+        for task_mutex in task_mutexes {
+            let mut task = task_mutex.lock().await;
+            task.refresh_speed();
+            stats.add(&task);
+        }
+        stats.finish()
+    }
+
+    /// 汇总多分P分组的状态和进度
+    pub async fn get_upload_groups(&self) -> Vec<UploadGroup> {
+        let task_mutexes: Vec<_> = self.upload_queue.lock().await.values().cloned().collect();
+        let mut group_ids = Vec::new();
+        for task_mutex in &task_mutexes {
+            if let Some(group_id) = task_mutex.lock().await.group_id()
+                && !group_ids.contains(&group_id)
             {
-                info!("任务切换至等待状态: {}", task_title!(task_mutex));
-                task_mutex.lock().await.pending();
-                return Ok(true);
+                group_ids.push(group_id);
             }
-            Ok(false)
-        } else {
-            Err(anyhow::anyhow!("任务ID不存在: {}", task_id))
         }
+
+        let mut groups = Vec::with_capacity(group_ids.len());
+        for group_id in group_ids {
+            let members = group_members(&self.upload_queue, &group_id).await;
+            let Some(first) = members.first() else {
+                continue;
+            };
+            let mut group = UploadGroup::new(group_id, &*first.lock().await);
+            for task_mutex in &members {
+                let mut task = task_mutex.lock().await;
+                task.refresh_speed();
+                group.add(&task);
+            }
+            groups.push(group.finish());
+        }
+        groups
     }
 
-    /// 暂停上传
-    pub async fn pause_upload(&mut self, task_id: &str) -> Result<bool> {
-        if let Some(task_mutex) = self.upload_queue.lock().await.get(task_id) {
-            info!("任务已暂停: {}", task_title!(task_mutex));
-            task_mutex.lock().await.pause();
-            Ok(true)
-        } else {
-            Err(anyhow::anyhow!("任务ID不存在: {}", task_id))
+    /// 上传视频，属于分组的任务连同同组的其他分P一起开始
+    pub async fn start_upload(&mut self, task_id: &str) -> Result<bool> {
+        info!("尝试开始任务: {}", task_id);
+        let mut started = false;
+        for task_mutex in group_of(&self.upload_queue, task_id).await? {
+            started |= self.start_task(&task_mutex).await;
         }
+        Ok(started)
+    }
+
+    async fn start_task(&self, task_mutex: &Arc<Mutex<UploadTask>>) -> bool {
+        let mut task = task_mutex.lock().await;
+        if task.is_paused() || task.is_failed() || task.is_waiting() {
+            info!("任务切换至等待状态: {}", task.title());
+            task.pending();
+            self.events.status_changed(&task);
+            return true;
+        }
+        false
+    }
+
+    /// 暂停上传，不再发起新的分片，超时仍未停止时直接结束后台任务并断开连接
+    pub async fn pause_upload(&mut self, task_id: &str) -> Result<bool> {
+        let Some(task_mutex) = self.upload_queue.lock().await.get(task_id).cloned() else {
+            return Err(anyhow::anyhow!("任务ID不存在: {}", task_id));
+        };
+        let generation = {
+            let mut task = task_mutex.lock().await;
+            info!("暂停任务: {}", task.title());
+            let generation = task.pause();
+            self.events.status_changed(&task);
+            generation
+        };
+
+        stop_after_grace(&self.upload_handle, task_mutex, task_id, generation);
+        Ok(true)
     }
 
     /// 取消上传
     pub async fn cancel_upload(&self, task_id: &str) -> Result<bool> {
-        if let Some(task_mutex) = self.upload_queue.lock().await.shift_remove(task_id) {
+        let removed = self.upload_queue.lock().await.shift_remove(task_id);
+        if let Some(task_mutex) = removed {
             task_mutex.lock().await.cancel();
 
             let handle = self.upload_handle.lock().await.remove(task_id);
@@ -165,68 +508,393 @@ impl UploadService {
                 info!("结束后台任务: {}", task_title!(task_mutex));
             }
             info!("取消任务成功: {}", task_title!(task_mutex));
+            self.events.task_removed(task_id);
+            self.save_queue().await;
             Ok(true)
         } else {
             Err(anyhow::anyhow!("任务ID不存在: {}", task_id))
         }
     }
 
+    /// 重试任务，上传会话仍有效时从未完成的分片继续；分组中的任务连同同组失败的分P一起重试
     pub async fn retry_upload(&self, task_id: &str) -> Result<bool> {
-        if let Some(task_mutex) = self.upload_queue.lock().await.get(task_id) {
-            task_mutex.lock().await.cancel();
+        for task_mutex in retry_targets(&self.upload_queue, task_id).await? {
+            self.retry_task(&task_mutex).await;
+        }
+        Ok(true)
+    }
 
-            let handle = self.upload_handle.lock().await.remove(task_id);
-            if let Some(handle) = handle {
-                handle.abort();
-                info!("结束后台任务: {}", task_title!(task_mutex));
+    async fn retry_task(&self, task_mutex: &Arc<Mutex<UploadTask>>) {
+        let task_id = task_mutex.lock().await.id.clone();
+        let handle = self.upload_handle.lock().await.remove(&task_id);
+        if let Some(handle) = handle {
+            handle.abort();
+            info!("结束后台任务: {}", task_title!(task_mutex));
+        }
+        let changed_path = {
+            let task = task_mutex.lock().await;
+            (task.error_kind == Some(ErrorKind::SourceChanged)).then(|| task.video.path.clone())
+        };
+        let source = match &changed_path {
+            Some(path) => SourceFingerprint::read_async(path).await.ok(),
+            None => None,
+        };
+        let mut task = task_mutex.lock().await;
+        if changed_path.is_some() {
+            // 手动重试视为确认使用改动后的文件，已上传的分片作废
+            task.source = source;
+            task.total_size = task.source.as_ref().map_or(0, |source| source.size);
+            task.reset_session();
+            info!("源文件已变化，重新记录并从头上传: {}", task.title());
+        }
+        task.retry();
+        self.events.status_changed(&task);
+        info!("任务重试: {}", task.title());
+    }
+}
+
+impl UploadService {
+    /// 投稿前确认分P的源文件在上传后没有被改动，改动过的任务标记为失败
+    pub async fn verify_sources(&self, task_ids: &[String]) -> Result<()> {
+        for task_id in task_ids {
+            let task_mutex = self.upload_queue.lock().await.get(task_id).cloned();
+            let Some(task_mutex) = task_mutex else {
+                continue;
+            };
+            match check_source(&task_mutex).await {
+                Ok(()) => {}
+                Err(e) if e.is::<SourceChanged>() => {
+                    let mut task = task_mutex.lock().await;
+                    error!("投稿前检查失败: {} - {}", task.title(), e);
+                    task.fail_with(ErrorKind::SourceChanged, e.to_string());
+                    self.events.status_changed(&task);
+                    return Err(e);
+                }
+                // 上传完成后删除源文件不影响投稿
+                Err(e) => warn!("无法检查源文件: {}", e),
             }
-            task_mutex.lock().await.retry();
-            info!("任务重试: {}", task_title!(task_mutex));
-            Ok(true)
-        } else {
-            Err(anyhow::anyhow!("任务ID不存在: {}", task_id))
         }
+        Ok(())
+    }
+}
+
+impl UploadService {
+    /// 按ID取得队列中的任务，顺序与 task_ids 相同
+    pub(crate) async fn tasks(&self, task_ids: &[String]) -> Result<Vec<Arc<Mutex<UploadTask>>>> {
+        let queue = self.upload_queue.lock().await;
+        task_ids
+            .iter()
+            .map(|task_id| {
+                queue
+                    .get(task_id)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("任务ID不存在: {}", task_id))
+            })
+            .collect()
+    }
+
+    /// 任务创建时记录的源文件指纹，上传完成后分P的路径会被清空，路径和哈希以此为准
+    pub async fn sources(&self, task_ids: &[String]) -> HashMap<String, SourceFingerprint> {
+        let task_mutexes: Vec<_> = {
+            let queue = self.upload_queue.lock().await;
+            task_ids
+                .iter()
+                .filter_map(|id| queue.get(id).cloned())
+                .collect()
+        };
+        source_map(&task_mutexes).await
+    }
+
+    /// 投稿前确认分组中的分P都已上传完成，避免某个分P失败时只投稿其余部分
+    pub async fn verify_groups(&self, task_ids: &[String]) -> Result<()> {
+        let task_mutexes: Vec<_> = {
+            let queue = self.upload_queue.lock().await;
+            task_ids
+                .iter()
+                .filter_map(|id| queue.get(id).cloned())
+                .collect()
+        };
+        check_groups(&self.upload_queue, &task_mutexes).await
     }
 }
 
 impl Drop for UploadService {
     fn drop(&mut self) {
-        let _ = block_on(self.stop_tx.send(()));
+        // 通道容量为 1，不需要等待；在异步上下文中释放时 block_on 会 panic
+        let _ = self.stop_tx.try_send(());
         info!("上传服务已停止");
     }
 }
 
+/// 最近一次写入磁盘的队列内容
+#[derive(Default)]
+struct SavedQueue {
+    content: String,
+    auths: String,
+    /// 命令行上传时不写入，避免覆盖桌面端的队列
+    disabled: bool,
+}
+
+/// 将队列快照写入磁盘，内容未变化时跳过
+async fn persist_queue(queue: &UploadQueue, saved_queue: &Arc<Mutex<SavedQueue>>) -> Result<()> {
+    let task_mutexes: Vec<_> = queue.lock().await.values().cloned().collect();
+    let mut tasks = Vec::with_capacity(task_mutexes.len());
+    for task_mutex in task_mutexes {
+        tasks.push(task_mutex.lock().await.clone());
+    }
+
+    let (content, auths) = snapshot(&tasks)?;
+    let mut saved = saved_queue.lock().await;
+    if saved.disabled || (saved.content == content && saved.auths == auths) {
+        return Ok(());
+    }
+
+    // 先写临时文件再替换，避免写入中途退出导致队列文件损坏
+    if saved.auths != auths {
+        write_atomic(&get_upload_sessions_json_path()?, &auths)?;
+        saved.auths = auths;
+    }
+    write_atomic(&get_upload_queue_json_path()?, &content)?;
+    saved.content = content;
+    trace!("上传队列已保存: {} 个任务", tasks.len());
+    Ok(())
+}
+
+/// 序列化队列和会话凭证，凭证不随任务序列化，按任务ID单独保存
+fn snapshot(tasks: &[UploadTask]) -> Result<(String, String)> {
+    let content = serde_json::to_string_pretty(tasks)?;
+    let auths: BTreeMap<&str, &str> = tasks
+        .iter()
+        .filter_map(|task| Some((task.id.as_str(), task.session.as_ref()?.auth.as_str())))
+        .collect();
+    Ok((content, serde_json::to_string(&auths)?))
+}
+
+/// 解析队列文件，填回会话凭证并修正任务状态
+fn restore_tasks(
+    content: &str,
+    mut auths: HashMap<String, String>,
+    auto_start: bool,
+) -> Result<Vec<UploadTask>> {
+    let mut tasks: Vec<UploadTask> = serde_json::from_str(content)?;
+    for task in &mut tasks {
+        // 旧版本的队列文件中凭证直接保存在会话里
+        if let Some(session) = &mut task.session
+            && let Some(auth) = auths.remove(&task.id)
+        {
+            session.auth = auth;
+        }
+        task.restore(auto_start);
+    }
+    Ok(tasks)
+}
+
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, content)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn load_session_auths() -> Result<HashMap<String, String>> {
+    let path = get_upload_sessions_json_path()?;
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = std::fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
 async fn upload_background(
-    queue: Arc<Mutex<IndexMap<String, Arc<Mutex<UploadTask>>>>>,
+    queue: UploadQueue,
     handle: Arc<Mutex<HashMap<String, task::JoinHandle<()>>>>,
     max_running: Arc<Mutex<u32>>,
+    saved_queue: Arc<Mutex<SavedQueue>>,
+    context: UploadContext,
     mut stop_rx: mpsc::Receiver<()>,
 ) {
     let mut one_sec = tokio::time::interval(tokio::time::Duration::from_secs(1));
+    let mut ticks: u64 = 0;
     loop {
         let queue_clone = Arc::clone(&queue);
         let handle_clone = Arc::clone(&handle);
         let max_running_clone = Arc::clone(&max_running);
         select! {
             _ = stop_rx.recv() => {
+                if let Err(e) = persist_queue(&queue, &saved_queue).await {
+                    error!("保存上传队列失败: {}", e);
+                }
                 info!("上传服务已停止");
                 return;
             }
             _ = one_sec.tick() => {
-                upload_background_interval(queue_clone, handle_clone, max_running_clone).await;
+                upload_background_interval(
+                    queue_clone,
+                    handle_clone,
+                    max_running_clone,
+                    context.clone(),
+                )
+                .await;
+
+                let auto_submit = context.config.lock().await.auto_upload;
+                context
+                    .pipeline
+                    .tick(&queue, &context.events, auto_submit)
+                    .await;
+
+                ticks += 1;
+                if ticks.is_multiple_of(PERSIST_INTERVAL_SECS)
+                    && let Err(e) = persist_queue(&queue, &saved_queue).await
+                {
+                    error!("保存上传队列失败: {}", e);
+                }
             }
         }
     }
 }
 
+/// 暂停后等待已开始的分片完成，超时仍未停止时结束后台任务并断开连接
+fn stop_after_grace(
+    upload_handle: &Arc<Mutex<HashMap<String, task::JoinHandle<()>>>>,
+    task_mutex: Arc<Mutex<UploadTask>>,
+    task_id: &str,
+    generation: u64,
+) {
+    let upload_handle = Arc::clone(upload_handle);
+    let task_id = task_id.to_string();
+    task::spawn(async move {
+        tokio::time::sleep(PAUSE_GRACE).await;
+        // 期间继续后又暂停时由新的计时器处理，避免结束新开始的上传
+        if !task_mutex.lock().await.is_paused_since(generation) {
+            return;
+        }
+        let handle = upload_handle.lock().await.remove(&task_id);
+        if let Some(handle) = handle
+            && !handle.is_finished()
+        {
+            handle.abort();
+            info!("暂停超时，结束后台任务: {}", task_title!(task_mutex));
+        }
+    });
+}
+
+/// 应用当前时段的规则，返回生效的最大并发数，以及该并发数是否来自时段规则
+async fn apply_schedule(
+    config: &Arc<Mutex<ConfigRoot>>,
+    max_running: &Arc<Mutex<u32>>,
+    bandwidth: &BandwidthLimits,
+) -> (u32, bool) {
+    // 配置中的全局带宽是用户设置的上限，时段规则只能在此基础上进一步收紧
+    let (max_curr, bandwidth_limit) = {
+        let config = config.lock().await;
+        match config.active_schedule(&chrono::Local::now()) {
+            Some(rule) => (
+                (rule.max_curr > 0).then_some(rule.max_curr),
+                min_limit(config.bandwidth_limit, rule.bandwidth_limit),
+            ),
+            None => (None, config.bandwidth_limit),
+        }
+    };
+
+    if bandwidth.global_rate() != bandwidth_limit {
+        info!("时段规则变化，全局带宽限制调整为: {} B/s", bandwidth_limit);
+        bandwidth.set_global(bandwidth_limit);
+    }
+
+    match max_curr {
+        Some(max_curr) => (max_curr, true),
+        None => (*max_running.lock().await, false),
+    }
+}
+
+/// 取两个带宽限制中较严格的一个，0 表示不限制
+fn min_limit(a: u64, b: u64) -> u64 {
+    match (a, b) {
+        (0, limit) | (limit, 0) => limit,
+        (a, b) => a.min(b),
+    }
+}
+
+/// 时段规则下调并发数时暂停最近开始的任务，记录为按时段暂停，有空闲名额时由 resume_scheduled 继续。
+/// 与手动暂停一样等待已开始的分片完成，暂停中的任务不再计入运行数
+async fn preempt_newest(
+    queue: &UploadQueue,
+    handle: &Arc<Mutex<HashMap<String, task::JoinHandle<()>>>>,
+    max_running: u32,
+    events: &UploadEvents,
+) {
+    let task_mutexes: Vec<_> = queue.lock().await.values().cloned().collect();
+    let mut running = Vec::new();
+    for task_mutex in task_mutexes {
+        let task = task_mutex.lock().await;
+        if task.is_running() {
+            running.push((task.started_at.unwrap_or(0), Arc::clone(&task_mutex)));
+        }
+    }
+    let count = running.len().saturating_sub(max_running as usize);
+    running.sort_by_key(|(started_at, _)| std::cmp::Reverse(*started_at));
+
+    for (_, task_mutex) in running.into_iter().take(count) {
+        let (task_id, generation) = {
+            let mut task = task_mutex.lock().await;
+            let generation = task.pause_for_schedule();
+            events.status_changed(&task);
+            info!("超出当前时段并发数，暂停任务: {}", task.title());
+            (task.id.clone(), generation)
+        };
+        stop_after_grace(handle, task_mutex, &task_id, generation);
+    }
+}
+
+/// 按时段暂停的任务按开始时间先后重新排队，最多 count 个，已上传的分片保留在会话中
+async fn resume_scheduled(queue: &UploadQueue, count: u32, events: &UploadEvents) {
+    let task_mutexes: Vec<_> = queue.lock().await.values().cloned().collect();
+    let mut paused = Vec::new();
+    for task_mutex in task_mutexes {
+        let task = task_mutex.lock().await;
+        if task.is_paused_by_schedule() {
+            paused.push((task.started_at.unwrap_or(0), Arc::clone(&task_mutex)));
+        }
+    }
+    paused.sort_by_key(|(started_at, _)| *started_at);
+
+    for (_, task_mutex) in paused.into_iter().take(count as usize) {
+        let mut task = task_mutex.lock().await;
+        task.pending();
+        events.status_changed(&task);
+        info!("时段并发数有空闲，继续任务: {}", task.title());
+    }
+}
+
 async fn upload_background_interval(
-    queue: Arc<Mutex<IndexMap<String, Arc<Mutex<UploadTask>>>>>,
+    queue: UploadQueue,
     handle: Arc<Mutex<HashMap<String, task::JoinHandle<()>>>>,
     max_running: Arc<Mutex<u32>>,
+    context: UploadContext,
 ) {
+    let UploadContext {
+        events,
+        bandwidth,
+        config,
+        ..
+    } = &context;
+    // 清理已经结束的后台任务，避免占用并发名额
+    handle.lock().await.retain(|_, h| !h.is_finished());
+    fail_groups(&queue, events).await;
+
+    let (max_running, scheduled) = apply_schedule(config, &max_running, bandwidth).await;
     let current_running = handle.lock().await.len() as u32;
-    let mut remain = *max_running.lock().await - current_running;
+    if current_running > max_running {
+        // 手动调低并发数时等待运行中的任务自然结束，只有时段规则会暂停任务
+        if scheduled {
+            preempt_newest(&queue, &handle, max_running, events).await;
+        }
+        return;
+    }
+
+    let mut remain = max_running - current_running;
     if remain > 0 {
+        resume_scheduled(&queue, remain, events).await;
         // debug!(
         //     "当前运行任务数: {}, 最大并行数: {}",
         //     current_running,
@@ -238,7 +906,41 @@ async fn upload_background_interval(
     }
 
     let task_mutexes: Vec<_> = queue.lock().await.values().cloned().collect();
-    for task_mutex in task_mutexes {
+    let running_ids: Vec<String> = handle.lock().await.keys().cloned().collect();
+    let mut running_by_uid: HashMap<u64, u32> = HashMap::new();
+    let mut candidates = Vec::new();
+    for (position, task_mutex) in task_mutexes.into_iter().enumerate() {
+        let (priority, uid) = {
+            let task = task_mutex.lock().await;
+            if running_ids.contains(&task.id) {
+                *running_by_uid.entry(task.user.uid).or_default() += 1;
+                continue;
+            }
+            if !task.is_pending() || task.is_backing_off() {
+                continue;
+            }
+            (task.priority, task.user.uid)
+        };
+        candidates.push((priority, position, uid, task_mutex));
+    }
+
+    let account_limits: HashMap<u64, u32> = config
+        .lock()
+        .await
+        .config
+        .iter()
+        .map(|(uid, user_config)| (*uid, user_config.max_concurrent))
+        .collect();
+    let mut order = StartOrder {
+        candidates,
+        running_by_uid,
+        account_limits,
+    };
+
+    while remain > 0 {
+        let Some((uid, task_mutex)) = order.pop() else {
+            break;
+        };
         // let task_guard = task_mutex.lock().await;
         // trace!(
         //     "任务：{}, 状态: {:?}",
@@ -247,57 +949,210 @@ async fn upload_background_interval(
         // );
         // drop(task_guard); // 显式释放锁
 
-        if task_mutex.lock().await.is_pending() && remain > 0 {
-            let task_id = task_mutex.lock().await.id.to_string();
-            if handle.lock().await.get(&task_id).is_some() {
-                continue;
+        if !task_mutex.lock().await.is_pending() {
+            continue;
+        }
+        let task_id = task_mutex.lock().await.id.to_string();
+        if handle.lock().await.get(&task_id).is_some() {
+            continue;
+        }
+        let task_mutex_clone = Arc::clone(&task_mutex);
+        let context = context.clone();
+        handle.lock().await.insert(
+            task_id,
+            task::spawn(async move {
+                let task = Arc::clone(&task_mutex_clone);
+                if let Err(e) = upload_impl(task, &context).await {
+                    handle_failure(&task_mutex_clone, e, &context.events).await;
+                }
+            }),
+        );
+        order.started(uid);
+        remain -= 1;
+    }
+}
+
+/// 等待开始的任务，候选为 (优先级, 队列位置, uid, 任务)
+struct StartOrder<T> {
+    candidates: Vec<(i32, usize, u64, T)>,
+    running_by_uid: HashMap<u64, u32>,
+    /// 各账号的并发上限，0 表示不限制
+    account_limits: HashMap<u64, u32>,
+}
+
+impl<T> StartOrder<T> {
+    fn running(&self, uid: u64) -> u32 {
+        self.running_by_uid.get(&uid).copied().unwrap_or(0)
+    }
+
+    fn under_limit(&self, uid: u64) -> bool {
+        let limit = self.account_limits.get(&uid).copied().unwrap_or(0);
+        limit == 0 || self.running(uid) < limit
+    }
+
+    /// 取出下一个可以开始的任务，账号已达并发上限的任务留到之后
+    fn pop(&mut self) -> Option<(u64, T)> {
+        // 优先级高的任务先开始，相同优先级时优先分配给运行任务较少的账号，再按队列中的位置
+        let index = self
+            .candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, uid, _))| self.under_limit(*uid))
+            .min_by_key(|(_, (priority, position, uid, _))| {
+                (std::cmp::Reverse(*priority), self.running(*uid), *position)
+            })
+            .map(|(index, _)| index)?;
+        let (_, _, uid, task) = self.candidates.remove(index);
+        Some((uid, task))
+    }
+
+    fn started(&mut self, uid: u64) {
+        *self.running_by_uid.entry(uid).or_default() += 1;
+    }
+}
+
+/// 分组的全部分P，按创建时间和队列位置排序
+async fn group_members(queue: &UploadQueue, group_id: &str) -> Vec<Arc<Mutex<UploadTask>>> {
+    let task_mutexes: Vec<_> = queue.lock().await.values().cloned().collect();
+    let mut members = Vec::new();
+    for (position, task_mutex) in task_mutexes.into_iter().enumerate() {
+        let task = task_mutex.lock().await;
+        if task.group_id().as_deref() == Some(group_id) {
+            members.push((task.created_at, position, Arc::clone(&task_mutex)));
+        }
+    }
+    members.sort_by_key(|(created_at, position, _)| (*created_at, *position));
+    members.into_iter().map(|(_, _, task)| task).collect()
+}
+
+/// 任务所在分组的全部分P，未分组时只有任务本身
+async fn group_of(queue: &UploadQueue, task_id: &str) -> Result<Vec<Arc<Mutex<UploadTask>>>> {
+    let task_mutex = queue
+        .lock()
+        .await
+        .get(task_id)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("任务ID不存在: {}", task_id))?;
+    let group_id = task_mutex.lock().await.group_id();
+    match group_id {
+        Some(group_id) => Ok(group_members(queue, &group_id).await),
+        None => Ok(vec![task_mutex]),
+    }
+}
+
+/// 重试时需要一并重试的任务：任务本身和同组中失败的分P
+async fn retry_targets(queue: &UploadQueue, task_id: &str) -> Result<Vec<Arc<Mutex<UploadTask>>>> {
+    let mut targets = Vec::new();
+    for task_mutex in group_of(queue, task_id).await? {
+        let task = task_mutex.lock().await;
+        if task.id == task_id || task.is_failed() {
+            drop(task);
+            targets.push(task_mutex);
+        }
+    }
+    Ok(targets)
+}
+
+/// 检查任务所在分组的分P是否全部上传完成，有分P失败或仍在上传时返回错误
+pub(crate) async fn check_groups(
+    queue: &UploadQueue,
+    tasks: &[Arc<Mutex<UploadTask>>],
+) -> Result<()> {
+    let mut checked = Vec::new();
+    for task_mutex in tasks {
+        let Some(group_id) = task_mutex.lock().await.group_id() else {
+            continue;
+        };
+        if checked.contains(&group_id) {
+            continue;
+        }
+        for member in group_members(queue, &group_id).await {
+            let task = member.lock().await;
+            if task.is_failed() {
+                anyhow::bail!("同组分P上传失败: {}", task.title());
+            }
+            if !task.is_completed() {
+                anyhow::bail!("同组分P尚未上传完成: {}", task.title());
             }
-            let task_mutex_clone = Arc::clone(&task_mutex);
-            handle.lock().await.insert(
-                task_id,
-                task::spawn(async move {
-                    let task = Arc::clone(&task_mutex_clone);
-                    if let Err(e) = upload_impl(task).await {
-                        error!("上传任务失败: {}", e);
-                        task_mutex_clone.lock().await.fail(e.to_string());
-                    }
-                }),
-            );
-            remain -= 1;
         }
+        checked.push(group_id);
+    }
+    Ok(())
+}
 
-        if remain == 0 {
-            break;
+/// 分组中有分P最终失败时，同组未开始的分P一并标记失败，等待整组重试
+async fn fail_groups(queue: &UploadQueue, events: &UploadEvents) {
+    let task_mutexes: Vec<_> = queue.lock().await.values().cloned().collect();
+    let mut failed: HashMap<String, String> = HashMap::new();
+    for task_mutex in &task_mutexes {
+        let task = task_mutex.lock().await;
+        if task.is_failed()
+            && task.error_kind != Some(ErrorKind::GroupFailed)
+            && let Some(group_id) = task.group_id()
+        {
+            failed.entry(group_id).or_insert_with(|| task.title());
         }
     }
+    if failed.is_empty() {
+        return;
+    }
+
+    for task_mutex in &task_mutexes {
+        let mut task = task_mutex.lock().await;
+        if !(task.is_pending() || task.is_waiting()) {
+            continue;
+        }
+        let Some(title) = task.group_id().and_then(|group_id| failed.get(&group_id)) else {
+            continue;
+        };
+        let message = format!("同组分P上传失败: {title}");
+        warn!("{}，停止任务: {}", message, task.title());
+        task.fail_with(ErrorKind::GroupFailed, message);
+        events.status_changed(&task);
+    }
 }
 
-/// 上传进度条结构体
-#[derive(Clone)]
+/// 未限速时每段的大小
+const PIECE_SIZE: usize = 1 << 20;
+/// 限速时每段的大小，较小的分段让速率更平滑
+const THROTTLED_PIECE_SIZE: usize = 64 << 10;
+
+/// 上传进度条结构体，发送前从限速器取得令牌
 pub struct ChunkedBuffer {
     bytes: Bytes,
     tx: mpsc::UnboundedSender<u64>,
+    limiters: Vec<Arc<RateLimiter>>,
+    piece: usize,
+    throttle: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
 }
 
 impl ChunkedBuffer {
-    pub fn new(bytes: Bytes, tx: mpsc::UnboundedSender<u64>) -> Self {
-        Self { bytes, tx }
+    pub fn new(
+        bytes: Bytes,
+        tx: mpsc::UnboundedSender<u64>,
+        limiters: Vec<Arc<RateLimiter>>,
+    ) -> Self {
+        Self {
+            bytes,
+            tx,
+            limiters,
+            piece: 0,
+            throttle: None,
+        }
+    }
+
+    fn is_throttled(&self) -> bool {
+        self.limiters.iter().any(|limiter| limiter.rate() > 0)
     }
 
     /// 发送进度并返回分片
     pub fn progress(&mut self) -> Result<Option<Bytes>> {
-        let pb = &self.tx;
-        let content_bytes = &mut self.bytes;
-        let n = content_bytes.remaining();
-        let pc = 1 << 20;
+        let n = self.piece.min(self.bytes.remaining());
         if n == 0 {
             Ok(None)
-        } else if n < pc {
-            pb.send(n as u64)?;
-            Ok(Some(content_bytes.copy_to_bytes(n)))
         } else {
-            pb.send(pc as u64)?;
-            Ok(Some(content_bytes.copy_to_bytes(pc)))
+            self.tx.send(n as u64)?;
+            Ok(Some(self.bytes.copy_to_bytes(n)))
         }
     }
 }
@@ -306,9 +1161,35 @@ impl Stream for ChunkedBuffer {
     type Item = Result<Bytes>;
     fn poll_next(
         mut self: Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
+        cx: &mut std::task::Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        match self.progress()? {
+        let this = &mut *self;
+        if this.throttle.is_none() {
+            let throttled = this.is_throttled();
+            let piece_size = if throttled {
+                THROTTLED_PIECE_SIZE
+            } else {
+                PIECE_SIZE
+            };
+            this.piece = this.bytes.remaining().min(piece_size);
+
+            if throttled && this.piece > 0 {
+                let limiters = this.limiters.clone();
+                let n = this.piece as u64;
+                this.throttle = Some(Box::pin(async move {
+                    for limiter in limiters {
+                        limiter.acquire(n).await;
+                    }
+                }));
+            }
+        }
+
+        if let Some(throttle) = this.throttle.as_mut() {
+            ready!(throttle.as_mut().poll(cx));
+            this.throttle = None;
+        }
+
+        match this.progress()? {
             None => Poll::Ready(None),
             Some(s) => Poll::Ready(Some(Ok(s))),
         }
@@ -321,18 +1202,282 @@ impl From<ChunkedBuffer> for Body {
     }
 }
 
-async fn upload_impl(task_mutex: Arc<Mutex<UploadTask>>) -> Result<()> {
+/// 单个分片的重试次数
+const CHUNK_RETRIES: u32 = 3;
+
+async fn read_chunk(filepath: &Path, start: u64, len: usize) -> Result<Bytes> {
+    let mut file = tokio::fs::File::open(filepath).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    let mut buf = vec![0; len];
+    file.read_exact(&mut buf).await?;
+    Ok(Bytes::from(buf))
+}
+
+/// 上传单个分片，网络错误时重试，会话失效时直接返回
+async fn upload_chunk(
+    client: &reqwest::Client,
+    session: &UploadSession,
+    filepath: &Path,
+    index: u32,
+    net_send_tx: mpsc::UnboundedSender<u64>,
+    limiters: &[Arc<RateLimiter>],
+) -> Result<UploadedPart> {
+    let (start, end) = session.chunk_range(index);
+    let chunk = read_chunk(filepath, start, (end - start) as usize).await?;
+
+    let mut attempt = 0;
+    loop {
+        // 用ChunkedBuffer 将每个chunk再拆分成1MB一小段
+        // 用ChunkedBuffer 内的net_send_tx来计算实际网络速度
+        let chunked_buffer =
+            ChunkedBuffer::new(chunk.clone(), net_send_tx.clone(), limiters.to_vec());
+        match session.upload_chunk(client, index, chunked_buffer).await {
+            Ok(etag) => return Ok(UploadedPart { index, etag }),
+            Err(e) if attempt < CHUNK_RETRIES && e.downcast_ref::<SessionRejected>().is_none() => {
+                attempt += 1;
+                warn!("分片 {} 上传失败，第 {} 次重试: {}", index, attempt, e);
+                tokio::time::sleep(tokio::time::Duration::from_secs(1 << attempt)).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// 分片上传过程中与 upload_impl 之间的通道
+struct PartChannels {
+    net_send_tx: mpsc::UnboundedSender<u64>,
+    chunk_done_tx: mpsc::UnboundedSender<UploadedPart>,
+    pause_rx: watch::Receiver<bool>,
+}
+
+/// 上传会话中缺失的分片并合并，暂停时返回 None，已完成的分片通过 chunk_done_tx 通知
+async fn upload_parts(
+    mut session: UploadSession,
+    filepath: PathBuf,
+    limit: usize,
+    proxy: Option<String>,
+    limiters: Vec<Arc<RateLimiter>>,
+    channels: PartChannels,
+) -> Result<Option<UploadedVideo>> {
+    let PartChannels {
+        net_send_tx,
+        chunk_done_tx,
+        pause_rx,
+    } = channels;
+    let client = upos::chunk_client(proxy.as_deref())?;
+    let missing = session.missing_chunks();
+    debug!(
+        "待上传分片: {}/{} ({})",
+        missing.len(),
+        session.chunks(),
+        session.upos_uri
+    );
+
+    let mut finished = Vec::with_capacity(missing.len());
+    {
+        // 暂停后不再发起新的分片，已经开始的分片继续传完
+        let mut parts = futures::stream::iter(missing)
+            .take_while(|_| futures::future::ready(!*pause_rx.borrow()))
+            .map(|index| {
+                upload_chunk(
+                    &client,
+                    &session,
+                    &filepath,
+                    index,
+                    net_send_tx.clone(),
+                    &limiters,
+                )
+            })
+            .buffer_unordered(limit);
+        while let Some(part) = parts.next().await {
+            let part = part?;
+            let _ = chunk_done_tx.send(part.clone());
+            finished.push(part);
+        }
+    }
+    for part in finished {
+        session.record_part(part);
+    }
+    if !session.missing_chunks().is_empty() {
+        return Ok(None);
+    }
+
+    let file_name = filepath
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    session.complete(&client, file_name).await.map(Some)
+}
+
+/// 源文件在创建任务后被改动，继续上传或投稿会得到损坏的视频
+#[derive(Debug)]
+pub struct SourceChanged(pub String);
+
+impl std::fmt::Display for SourceChanged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "源文件已变化: {}", self.0)
+    }
+}
+
+impl std::error::Error for SourceChanged {}
+
+/// 按任务ID收集源文件指纹，旧版本恢复且未检查过的任务没有指纹
+pub(crate) async fn source_map(
+    task_mutexes: &[Arc<Mutex<UploadTask>>],
+) -> HashMap<String, SourceFingerprint> {
+    let mut sources = HashMap::new();
+    for task_mutex in task_mutexes {
+        let task = task_mutex.lock().await;
+        if let Some(source) = &task.source {
+            sources.insert(task.id.clone(), source.clone());
+        }
+    }
+    sources
+}
+
+/// 确认源文件与创建任务时一致，旧版本恢复的任务在第一次检查时补充记录
+pub(crate) async fn check_source(task_mutex: &Arc<Mutex<UploadTask>>) -> Result<()> {
+    let (path, expected) = {
+        let task = task_mutex.lock().await;
+        let path = match &task.source {
+            Some(source) => source.path.clone(),
+            None => task.video.path.clone(),
+        };
+        (PathBuf::from(path), task.source.clone())
+    };
+    if path.as_os_str().is_empty() {
+        return Ok(());
+    }
+
+    let current = SourceFingerprint::read_async(&path)
+        .await
+        .with_context(|| format!("源文件不可用: {}", path.display()))?;
+    match expected {
+        Some(expected) => {
+            if let Some(diff) = expected.diff(&current) {
+                return Err(SourceChanged(format!("{} ({})", path.display(), diff)).into());
+            }
+        }
+        None => task_mutex.lock().await.source = Some(current),
+    }
+    Ok(())
+}
+
+/// 判断失败原因，网络和服务器问题可以重试，登录失效和文件缺失需要人工处理
+fn classify_error(e: &anyhow::Error) -> ErrorKind {
+    for cause in e.chain() {
+        if cause.is::<LoginRequired>() {
+            return ErrorKind::Auth;
+        }
+        if cause.is::<SessionRejected>() {
+            return ErrorKind::SessionExpired;
+        }
+        if cause.is::<SourceChanged>() {
+            return ErrorKind::SourceChanged;
+        }
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return match e.status() {
+                Some(reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => {
+                    ErrorKind::Auth
+                }
+                Some(status) if status.is_server_error() => ErrorKind::Server,
+                // 返回的内容无法解析，一般是服务器临时异常
+                _ if e.is_decode() => ErrorKind::Server,
+                Some(_) => ErrorKind::Unknown,
+                None => ErrorKind::Network,
+            };
+        }
+        if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            return match e.kind() {
+                std::io::ErrorKind::NotFound
+                | std::io::ErrorKind::PermissionDenied
+                | std::io::ErrorKind::UnexpectedEof => ErrorKind::SourceMissing,
+                _ => ErrorKind::Unknown,
+            };
+        }
+    }
+    ErrorKind::Unknown
+}
+
+/// 记录失败原因，可重试的错误按退避时间自动重试，否则标记为失败，暂停中的任务保持暂停
+async fn handle_failure(
+    task_mutex: &Arc<Mutex<UploadTask>>,
+    e: anyhow::Error,
+    events: &UploadEvents,
+) {
+    let kind = classify_error(&e);
+    let mut task = task_mutex.lock().await;
+    // 暂停后等待分片完成期间出错时保持暂停，继续时再重新上传
+    if task.is_paused() {
+        warn!("任务暂停中上传失败，保持暂停: {} - {}", task.title(), e);
+        return;
+    }
+    let policy = task.config().lock().await.retry.clone();
+    task.record_attempt(kind, e.to_string());
+
+    // 第一次尝试也计入次数
+    if kind.is_retryable() && task.auto_retries + 1 < policy.max_attempts {
+        // 还没有上传任何分片时换一条线路重新开始
+        if task
+            .session
+            .as_ref()
+            .is_some_and(|session| session.parts.is_empty())
+        {
+            task.reset_session();
+        }
+        let delay = policy.delay(task.auto_retries + 1);
+        warn!(
+            "上传任务失败 ({:?})，{} 秒后第 {} 次自动重试: {} - {}",
+            kind,
+            delay.as_secs(),
+            task.auto_retries + 1,
+            task.title(),
+            e
+        );
+        task.schedule_retry(delay, e.to_string());
+    } else {
+        error!("上传任务失败 ({:?}): {} - {}", kind, task.title(), e);
+        task.fail_with(kind, e.to_string());
+    }
+    events.status_changed(&task);
+}
+
+/// 根据重试次数选择线路，避免重复使用失败的线路，有测速结果时按测速排名轮换
+fn select_line(line: Option<&str>, retry_count: u32, ranked: &[String]) -> String {
+    // 定义可用的上传线路列表
+    let available_lines = [
+        "auto", "bda2", "ws", "qn", "bldsa", "tx", "txa", "bda", "alia",
+    ];
+
+    match line {
+        // 如果用户配置了特定线路，第一次使用配置线路，之后重试时自动切换
+        Some(config_line) if retry_count == 0 && config_line != "auto" => config_line.to_string(),
+        _ if !ranked.is_empty() => ranked[(retry_count as usize) % ranked.len()].clone(),
+        Some(config_line) if retry_count == 0 => config_line.to_string(),
+        // 根据重试次数选择不同的线路
+        _ => available_lines[(retry_count as usize) % available_lines.len()].to_string(),
+    }
+}
+
+async fn upload_impl(task_mutex: Arc<Mutex<UploadTask>>, context: &UploadContext) -> Result<()> {
+    let UploadContext {
+        events, bandwidth, ..
+    } = context;
     info!("开始上传任务: {}", task_title!(task_mutex));
-    task_mutex.lock().await.start();
+    {
+        let mut task = task_mutex.lock().await;
+        task.start();
+        events.status_changed(&task);
+    }
     let uid = task_mutex.lock().await.user.uid;
 
-    let (line, _proxy, limit) = {
+    let (line, proxy, limit) = {
         let lock = task_mutex.lock().await;
         let config_root = lock.config().lock().await;
         let config = config_root
             .config
             .get(&uid)
-            .ok_or_else(|| anyhow::anyhow!("用户未登录或不存在"))?;
+            .ok_or_else(|| LoginRequired("用户未登录或不存在".to_string()))?;
 
         (config.line.clone(), config.proxy.clone(), config.limit)
     };
@@ -344,109 +1489,493 @@ async fn upload_impl(task_mutex: Arc<Mutex<UploadTask>>) -> Result<()> {
         .lock()
         .await
         .get(&uid)
-        .ok_or_else(|| anyhow::anyhow!("用户未登录或不存在"))?
+        .ok_or_else(|| LoginRequired("用户未登录或不存在".to_string()))?
         .bilibili
         .clone();
 
     let retry_count = task_mutex.lock().await.retry_count();
+    let filepath = PathBuf::from(&task_mutex.lock().await.video.path);
+    let total_size = task_mutex.lock().await.total_size;
+    // 文件不存在或已被改动时直接失败，不再重试
+    check_source(&task_mutex).await?;
 
-    // 定义可用的上传线路列表
-    let available_lines = [
-        "auto", "bda2", "ws", "qn", "bldsa", "tx", "txa", "bda", "alia",
-    ];
-
-    // 根据重试次数自动选择线路
-    let selected_line = if let Some(config_line) = &line {
-        // 如果用户配置了特定线路，第一次使用配置线路，之后重试时自动切换
-        if retry_count == 0 {
-            config_line.clone()
-        } else {
-            // 根据重试次数选择不同的线路，避免重复使用失败的线路
-            let line_index = (retry_count as usize) % available_lines.len();
-            available_lines[line_index].to_string()
+    // 会话仍然有效时从缺失的分片继续上传
+    let resumable = task_mutex
+        .lock()
+        .await
+        .session
+        .clone()
+        .filter(|session| session.is_resumable(total_size));
+    let session = match resumable {
+        Some(session) => {
+            info!(
+                "继续上传会话: {} (线路: {}, 已完成分片: {}/{})",
+                task_title!(task_mutex),
+                session.line,
+                session.parts.len(),
+                session.chunks()
+            );
+            session
         }
-    } else {
-        // 如果没有配置线路，根据重试次数自动选择
-        let line_index = (retry_count as usize) % available_lines.len();
-        available_lines[line_index].to_string()
-    };
+        None => {
+            let ranked = context
+                .line_benchmarks
+                .lock()
+                .await
+                .get(&uid)
+                .filter(|benchmark| benchmark.is_fresh(proxy.as_deref()))
+                .map(AccountBenchmark::ranked_lines)
+                .unwrap_or_default();
+            let selected_line = select_line(line.as_deref(), retry_count, &ranked);
+            info!("选择线路: {} (重试次数: {})", selected_line, retry_count);
 
-    info!("选择线路: {} (重试次数: {})", selected_line, retry_count);
-
-    let probe = match selected_line.as_str() {
-        "bda2" => line::bda2(),
-        "ws" => line::ws(),
-        "qn" => line::qn(),
-        "bldsa" => line::bldsa(),
-        "tx" => line::tx(),
-        "txa" => line::txa(),
-        "bda" => line::bda(),
-        "alia" => line::alia(),
-        _ => line::Probe::probe(&client.client).await?,
+            let selected_line = match selected_line.as_str() {
+                "auto" => upos::probe(&upos::chunk_client(proxy.as_deref())?).await?,
+                other => other,
+            };
+            let session =
+                UploadSession::pre_upload(client, selected_line, &filepath, total_size).await?;
+            task_mutex.lock().await.set_session(session.clone());
+            session
+        }
     };
 
-    let filepath = PathBuf::from(&task_mutex.lock().await.video.path);
-    let video_file = VideoFile::new(&filepath)?;
-    let total_size = task_mutex.lock().await.total_size;
-    let parcel = probe.pre_upload(client, video_file).await?;
-    let (file_read_tx, mut file_read_rx) = mpsc::unbounded_channel();
+    let (chunk_done_tx, mut chunk_done_rx) = mpsc::unbounded_channel();
     let (net_send_tx, mut net_send_rx) = mpsc::unbounded_channel();
-    let mut uploaded = 0;
-    let mut video_fut = Box::pin(
-        parcel.upload(StatelessClient::default(), limit as usize, |vs| {
-            vs.map(|chunk| {
-                // parcel.upload 将文件分成各个chunk
-                // 用ChunkedBuffer 将每个chunk再拆分成1MB一小段
-                // 用ChunkedBuffer 内的net_send_tx来计算实际网络速度
-                // 用tx来计算总的上传进度，防止重传的数据进入net_send_tx导致进度条计算出错
-                let chunk = chunk?;
-                let len = chunk.len();
-                uploaded += len;
-                file_read_tx.send(uploaded).unwrap();
-                let chunked_buffer = ChunkedBuffer::new(chunk, net_send_tx.clone());
-                Ok((chunked_buffer, len))
-            })
-        }),
-    );
+    let (pause_tx, pause_rx) = watch::channel(false);
+    // 用chunk_done_tx来计算总的上传进度，防止重传的数据进入net_send_tx导致进度条计算出错
+    let mut video_fut = Box::pin(upload_parts(
+        session,
+        filepath,
+        (limit as usize).max(1),
+        proxy,
+        bandwidth.limiters_for(uid),
+        PartChannels {
+            net_send_tx,
+            chunk_done_tx,
+            pause_rx,
+        },
+    ));
 
-    let mut paused = false;
+    let mut pause_check = tokio::time::interval(tokio::time::Duration::from_millis(500));
     loop {
-        while task_mutex.lock().await.is_paused() {
-            paused = true;
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        }
-
-        if paused {
-            paused = false;
-            if task_mutex.lock().await.is_cancelled() {
-                info!("任务已取消: {}", task_title!(task_mutex));
-                return Ok(());
-            }
-            info!("任务已恢复: {}", task_title!(task_mutex));
-            task_mutex.lock().await.start();
-        }
-
         select! {
+            _ = pause_check.tick(), if !*pause_tx.borrow() => {
+                if task_mutex.lock().await.is_paused() {
+                    info!("任务暂停中，等待已开始的分片完成: {}", task_title!(task_mutex));
+                    let _ = pause_tx.send(true);
+                }
+            }
             Some(total_transmit) = net_send_rx.recv() => {
                 // 处理分片
-                task_mutex.lock().await.update_total_transmit_bytes(total_transmit);
+                let mut task = task_mutex.lock().await;
+                task.update_total_transmit_bytes(total_transmit);
+                events.progress(&task);
             }
-            Some(process_so_far) = file_read_rx.recv() => {
-                // 处理上传进度
-                task_mutex.lock().await.update_progress(process_so_far as f64/total_size as f64 * 100.0);
+            Some(part) = chunk_done_rx.recv() => {
+                // 记录已完成的分片并更新上传进度
+                let mut task = task_mutex.lock().await;
+                task.record_chunk(part);
+                events.progress(&task);
             }
-            Ok(return_video) = &mut video_fut => {
+            result = &mut video_fut => {
+                let return_video = match result {
+                    Ok(Some(return_video)) => return_video,
+                    Ok(None) => {
+                        // 暂停前完成的分片已记录在会话中，恢复后从下一个分片继续
+                        let mut task = task_mutex.lock().await;
+                        while let Ok(part) = chunk_done_rx.try_recv() {
+                            task.record_chunk(part);
+                        }
+                        info!("任务已暂停: {} ({:.1}%)", task.title(), task.progress);
+                        task.refresh_speed();
+                        events.task_updated(&task);
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        if e.downcast_ref::<SessionRejected>().is_some() {
+                            warn!("上传会话失效，下次将重新上传: {}", task_title!(task_mutex));
+                            task_mutex.lock().await.reset_session();
+                        }
+                        return Err(e);
+                    }
+                };
                 // 处理视频上传完成
                 debug!("视频上传完成: {:?}", return_video);
                 // rewrite the stored id & name
                 task_mutex.lock().await.video.filename = return_video.filename.clone();
                 task_mutex.lock().await.video.path.clear();
-                task_mutex.lock().await.video.cid = return_video.cid as u64;
+                task_mutex.lock().await.video.cid = return_video.cid;
                 info!("上传任务完成: {} -> {}", task_title!(task_mutex), return_video.filename);
-                task_mutex.lock().await.complete();
+                let mut task = task_mutex.lock().await;
+                task.complete();
+                events.status_changed(&task);
 
                 return Ok(())
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TaskStatus;
+
+    fn line_result(line: &str, error: bool) -> LineBenchmark {
+        LineBenchmark {
+            line: line.to_string(),
+            latency_ms: Some(10),
+            throughput: (!error).then_some(1024.0),
+            error: error.then(|| "探测失败".to_string()),
+        }
+    }
+
+    fn ranked(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    fn start_order(
+        candidates: &[(i32, u64, &'static str)],
+        running: &[(u64, u32)],
+        limits: &[(u64, u32)],
+    ) -> StartOrder<&'static str> {
+        StartOrder {
+            candidates: candidates
+                .iter()
+                .enumerate()
+                .map(|(position, (priority, uid, id))| (*priority, position, *uid, *id))
+                .collect(),
+            running_by_uid: running.iter().copied().collect(),
+            account_limits: limits.iter().copied().collect(),
+        }
+    }
+
+    /// 依次取出任务并视为已开始
+    fn drain(mut order: StartOrder<&'static str>) -> Vec<&'static str> {
+        let mut started = Vec::new();
+        while let Some((uid, id)) = order.pop() {
+            order.started(uid);
+            started.push(id);
+        }
+        started
+    }
+
+    #[test]
+    fn starts_by_priority_then_position() {
+        let order = start_order(
+            &[(0, 1, "a"), (5, 1, "b"), (0, 1, "c"), (5, 1, "d")],
+            &[],
+            &[],
+        );
+        assert_eq!(drain(order), ["b", "d", "a", "c"]);
+    }
+
+    #[test]
+    fn same_priority_prefers_idle_accounts() {
+        let order = start_order(&[(0, 1, "a"), (0, 1, "b"), (0, 2, "c")], &[(1, 1)], &[]);
+        assert_eq!(drain(order), ["c", "a", "b"]);
+
+        // 优先级高于账号均衡
+        let order = start_order(&[(1, 1, "a"), (0, 2, "b")], &[(1, 3)], &[]);
+        assert_eq!(drain(order), ["a", "b"]);
+    }
+
+    #[test]
+    fn respects_account_concurrency_limit() {
+        let order = start_order(
+            &[(0, 1, "a"), (0, 1, "b"), (0, 2, "c"), (0, 3, "d")],
+            &[(2, 1)],
+            &[(1, 1), (2, 1)],
+        );
+        // 账号 2 已有任务在运行，账号 1 开始一个后也达到上限，账号 3 不限制
+        assert_eq!(drain(order), ["a", "d"]);
+
+        let order = start_order(&[(9, 1, "a"), (0, 2, "b")], &[(1, 2)], &[(1, 2), (2, 0)]);
+        assert_eq!(drain(order), ["b"]);
+    }
+
+    fn grouped(id: &str, group_key: &str, created_at: usize, status: TaskStatus) -> UploadTask {
+        let mut video = VideoInfo::from_path(id.to_string(), Path::new("/videos/part.mp4"));
+        video.group_key = group_key.to_string();
+        let mut task = UploadTask::new(
+            &User::new(1, "user".to_string(), String::new()),
+            "template",
+            &video,
+            None,
+            Arc::new(Mutex::new(ConfigRoot::default())),
+            Arc::new(Mutex::new(HashMap::new())),
+        );
+        task.created_at = created_at;
+        task.status = status;
+        task
+    }
+
+    fn queue_of(tasks: Vec<UploadTask>) -> UploadQueue {
+        Arc::new(Mutex::new(
+            tasks
+                .into_iter()
+                .map(|task| (task.id.clone(), Arc::new(Mutex::new(task))))
+                .collect(),
+        ))
+    }
+
+    async fn ids(tasks: &[Arc<Mutex<UploadTask>>]) -> Vec<String> {
+        let mut ids = Vec::new();
+        for task in tasks {
+            ids.push(task.lock().await.id.clone());
+        }
+        ids
+    }
+
+    async fn status(queue: &UploadQueue, id: &str) -> TaskStatus {
+        let task_mutex = queue.lock().await.get(id).cloned().unwrap();
+        task_mutex.lock().await.status.clone()
+    }
+
+    #[tokio::test]
+    async fn group_members_start_together_in_part_order() {
+        let queue = queue_of(vec![
+            grouped("p2", "live", 20, TaskStatus::Waiting),
+            grouped("solo", "", 5, TaskStatus::Waiting),
+            grouped("p1", "live", 10, TaskStatus::Waiting),
+            grouped("p3", "live", 20, TaskStatus::Waiting),
+            grouped("other", "vod", 1, TaskStatus::Waiting),
+        ]);
+        let members = group_of(&queue, "p3").await.unwrap();
+        assert_eq!(ids(&members).await, ["p1", "p2", "p3"]);
+        assert_eq!(
+            ids(&group_of(&queue, "solo").await.unwrap()).await,
+            ["solo"]
+        );
+        assert!(group_of(&queue, "missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn retry_includes_failed_members_only() {
+        let queue = queue_of(vec![
+            grouped("p1", "live", 1, TaskStatus::Completed),
+            grouped("p2", "live", 2, TaskStatus::Failed),
+            grouped("p3", "live", 3, TaskStatus::Failed),
+            grouped("p4", "live", 4, TaskStatus::Paused),
+        ]);
+        let targets = retry_targets(&queue, "p3").await.unwrap();
+        assert_eq!(ids(&targets).await, ["p2", "p3"]);
+        let targets = retry_targets(&queue, "p4").await.unwrap();
+        assert_eq!(ids(&targets).await, ["p2", "p3", "p4"]);
+    }
+
+    #[tokio::test]
+    async fn submit_waits_for_the_whole_group() {
+        let queue = queue_of(vec![
+            grouped("p1", "live", 1, TaskStatus::Completed),
+            grouped("p2", "live", 2, TaskStatus::Running),
+            grouped("solo", "", 3, TaskStatus::Completed),
+        ]);
+        let p1 = queue.lock().await.get("p1").cloned().unwrap();
+        let solo = queue.lock().await.get("solo").cloned().unwrap();
+        // 只投稿第一个分P时同样要等整组完成
+        let err = check_groups(&queue, &[Arc::clone(&p1)]).await.unwrap_err();
+        assert!(err.to_string().contains("尚未上传完成"));
+        assert!(check_groups(&queue, &[Arc::clone(&solo)]).await.is_ok());
+
+        let p2 = queue.lock().await.get("p2").cloned().unwrap();
+        p2.lock().await.fail("network".to_string());
+        let err = check_groups(&queue, &[Arc::clone(&p1)]).await.unwrap_err();
+        assert!(err.to_string().contains("上传失败"));
+
+        p2.lock().await.complete();
+        assert!(check_groups(&queue, &[p1, solo]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn failed_part_stops_unstarted_members() {
+        let queue = queue_of(vec![
+            grouped("p1", "live", 1, TaskStatus::Failed),
+            grouped("p2", "live", 2, TaskStatus::Pending),
+            grouped("p3", "live", 3, TaskStatus::Waiting),
+            grouped("p4", "live", 4, TaskStatus::Completed),
+            grouped("p5", "live", 5, TaskStatus::Paused),
+            grouped("other", "vod", 6, TaskStatus::Pending),
+        ]);
+        let events = UploadEvents::new();
+        fail_groups(&queue, &events).await;
+        for id in ["p2", "p3"] {
+            let task_mutex = queue.lock().await.get(id).cloned().unwrap();
+            let task = task_mutex.lock().await;
+            assert!(task.is_failed());
+            assert_eq!(task.error_kind, Some(ErrorKind::GroupFailed));
+        }
+        assert!(matches!(status(&queue, "p4").await, TaskStatus::Completed));
+        assert!(matches!(status(&queue, "p5").await, TaskStatus::Paused));
+        assert!(matches!(status(&queue, "other").await, TaskStatus::Pending));
+    }
+
+    #[test]
+    fn group_status_summarizes_parts() {
+        let summarize = |parts: &[(TaskStatus, u64, f64)]| {
+            let tasks: Vec<UploadTask> = parts
+                .iter()
+                .enumerate()
+                .map(|(i, (status, size, progress))| {
+                    let mut task = grouped(&format!("p{i}"), "live", i, status.clone());
+                    task.total_size = *size;
+                    task.progress = *progress;
+                    task
+                })
+                .collect();
+            let mut group = UploadGroup::new("group".to_string(), &tasks[0]);
+            for task in &tasks {
+                group.add(task);
+            }
+            group.finish()
+        };
+
+        let group = summarize(&[
+            (TaskStatus::Completed, 100, 100.0),
+            (TaskStatus::Paused, 300, 50.0),
+        ]);
+        assert!(matches!(group.status, TaskStatus::Paused));
+        assert_eq!(group.progress, 62.5);
+        assert_eq!(group.task_ids, ["p0", "p1"]);
+
+        let group = summarize(&[
+            (TaskStatus::Running, 100, 0.0),
+            (TaskStatus::Failed, 100, 0.0),
+        ]);
+        assert!(matches!(group.status, TaskStatus::Failed));
+        let group = summarize(&[
+            (TaskStatus::Waiting, 100, 0.0),
+            (TaskStatus::Running, 100, 0.0),
+        ]);
+        assert!(matches!(group.status, TaskStatus::Running));
+        let group = summarize(&[
+            (TaskStatus::Completed, 100, 0.0),
+            (TaskStatus::Completed, 100, 0.0),
+        ]);
+        assert!(matches!(group.status, TaskStatus::Completed));
+        assert_eq!(group.progress, 100.0);
+    }
+
+    fn saved_session(total_size: u64, auth: &str) -> UploadSession {
+        UploadSession {
+            line: "bda2".to_string(),
+            url: "https://upos-cs-upcdnbda2.bilivideo.com/ugcfx2lf/n1.mp4".to_string(),
+            auth: auth.to_string(),
+            upload_id: "upload".to_string(),
+            upos_uri: "upos://ugcfx2lf/n1.mp4".to_string(),
+            biz_id: 1,
+            chunk_size: total_size / 4,
+            total_size,
+            created_at: 0,
+            parts: Vec::new(),
+        }
+    }
+
+    fn saved_queue() -> (String, String) {
+        let mut running = grouped("running", "", 1, TaskStatus::Waiting);
+        running.total_size = 400;
+        running.start();
+        running.set_session(saved_session(400, "secret"));
+        running.record_chunk(UploadedPart {
+            index: 0,
+            etag: "etag-0".to_string(),
+        });
+        running.update_total_transmit_bytes(150);
+        let mut paused = grouped("paused", "", 2, TaskStatus::Paused);
+        paused.priority = 3;
+        let mut completed = grouped("completed", "", 3, TaskStatus::Waiting);
+        completed.total_size = 100;
+        completed.complete();
+        snapshot(&[running, paused, completed]).unwrap()
+    }
+
+    #[test]
+    fn saved_queue_keeps_auth_out_of_tasks() {
+        let (content, auths) = saved_queue();
+        assert!(!content.contains("secret"));
+        assert_eq!(auths, r#"{"running":"secret"}"#);
+    }
+
+    #[test]
+    fn restore_round_trip() {
+        let (content, auths) = saved_queue();
+        let auths: HashMap<String, String> = serde_json::from_str(&auths).unwrap();
+        let tasks = restore_tasks(&content, auths.clone(), true).unwrap();
+        let ids: Vec<&str> = tasks.iter().map(|task| task.id.as_str()).collect();
+        assert_eq!(ids, ["running", "paused", "completed"]);
+
+        let running = &tasks[0];
+        assert!(running.is_pending());
+        assert_eq!(running.progress, 25.0);
+        assert_eq!(running.total_transmit_bytes, 0);
+        assert_eq!(running.speed_bytes_per_sec, 0.0);
+        let session = running.session.as_ref().unwrap();
+        assert_eq!(session.auth, "secret");
+        assert_eq!(session.missing_chunks(), [1, 2, 3]);
+
+        assert!(tasks[1].is_paused());
+        assert_eq!(tasks[1].priority, 3);
+        assert!(tasks[2].is_completed());
+        assert_eq!(tasks[2].progress, 100.0);
+
+        // 未开启自动开始时上次运行中的任务恢复为暂停
+        let tasks = restore_tasks(&content, auths, false).unwrap();
+        assert!(tasks[0].is_paused());
+    }
+
+    #[test]
+    fn restore_without_auth_keeps_session_empty() {
+        let (content, _) = saved_queue();
+        let tasks = restore_tasks(&content, HashMap::new(), true).unwrap();
+        assert_eq!(tasks[0].session.as_ref().unwrap().auth, "");
+    }
+
+    #[test]
+    fn select_line_prefers_configured_line_first() {
+        let ranked = ranked(&["qn", "ws"]);
+        assert_eq!(select_line(Some("tx"), 0, &ranked), "tx");
+        // 重试时按测速排名轮换
+        assert_eq!(select_line(Some("tx"), 1, &ranked), "ws");
+        assert_eq!(select_line(Some("tx"), 2, &ranked), "qn");
+    }
+
+    #[test]
+    fn select_line_uses_ranking_for_auto() {
+        let ranked = ranked(&["qn", "ws", "bda2"]);
+        assert_eq!(select_line(Some("auto"), 0, &ranked), "qn");
+        assert_eq!(select_line(None, 0, &ranked), "qn");
+        assert_eq!(select_line(None, 4, &ranked), "ws");
+    }
+
+    #[test]
+    fn select_line_falls_back_without_ranking() {
+        assert_eq!(select_line(Some("auto"), 0, &[]), "auto");
+        assert_eq!(select_line(None, 0, &[]), "auto");
+        assert_eq!(select_line(Some("tx"), 1, &[]), "bda2");
+        assert_eq!(select_line(None, 9, &[]), "auto");
+    }
+
+    #[test]
+    fn benchmark_ranking_skips_failed_lines() {
+        let benchmark = AccountBenchmark {
+            uid: 1,
+            proxy: None,
+            tested_at: chrono::Utc::now().timestamp_millis() as usize,
+            lines: vec![
+                line_result("qn", false),
+                line_result("ws", true),
+                line_result("bda2", false),
+            ],
+        };
+        assert_eq!(benchmark.ranked_lines(), ["qn", "bda2"]);
+        assert!(benchmark.is_fresh(None));
+        // 代理变化或过期后不再使用
+        assert!(!benchmark.is_fresh(Some("http://127.0.0.1:7890")));
+        let expired = AccountBenchmark {
+            tested_at: benchmark.tested_at - BENCHMARK_TTL_MS,
+            ..benchmark
+        };
+        assert!(!expired.is_fresh(None));
+    }
+}
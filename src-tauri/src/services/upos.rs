@@ -0,0 +1,527 @@
+use std::{
+    fmt,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use biliup::bilibili::BiliBili;
+use futures::future::join_all;
+use reqwest::{Body, StatusCode, header::CONTENT_LENGTH};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::{debug, info};
+
+/// 上传会话的有效期（毫秒），超过后重新预上传
+const SESSION_TTL_MS: usize = 24 * 60 * 60 * 1000;
+
+/// 预上传时声明的客户端版本和上传分片的 User-Agent，与 Cargo.toml 中固定版本的 biliup 一致，
+/// 升级 biliup 时一并更新
+const UPLOAD_CLIENT_VERSION: &str = "2.8.12";
+const UPLOAD_CLIENT_BUILD: u64 = 2081200;
+const UPLOAD_USER_AGENT: &str =
+    "Mozilla/5.0 (X11; Linux x86_64; rv:60.1) Gecko/20100101 Firefox/60.1";
+
+/// 可用的上传线路及其预上传参数
+pub const LINES: [(&str, &str); 8] = [
+    ("bda2", "probe_version=20221109&upcdn=bda2&zone=cs"),
+    ("ws", "probe_version=20221109&upcdn=ws&zone=cs"),
+    ("qn", "probe_version=20221109&upcdn=qn&zone=cs"),
+    ("bldsa", "probe_version=20221109&upcdn=bldsa&zone=cs"),
+    ("tx", "probe_version=20221109&upcdn=tx&zone=cs"),
+    ("txa", "probe_version=20221109&upcdn=txa&zone=cs"),
+    ("bda", "probe_version=20221109&upcdn=bda&zone=cs"),
+    ("alia", "probe_version=20221109&upcdn=alia&zone=cs"),
+];
+
+fn line_query(line: &str) -> Option<&'static str> {
+    LINES
+        .iter()
+        .find(|(name, _)| *name == line)
+        .map(|(_, query)| *query)
+}
+
+fn probe_url(line: &str) -> String {
+    format!("https://upos-cs-upcdn{line}.bilivideo.com/OK")
+}
+
+/// 请求线路的探测地址，返回响应时间
+async fn probe_line(client: &reqwest::Client, line: &str) -> Result<Duration> {
+    let start = Instant::now();
+    let resp = client
+        .get(probe_url(line))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        anyhow::bail!("{}", resp.status());
+    }
+    Ok(start.elapsed())
+}
+
+/// 并发请求各线路的探测地址，返回响应最快的线路
+///
+/// 需要传入上传分片使用的客户端，保证探测和上传走同一个代理
+pub async fn probe(client: &reqwest::Client) -> Result<&'static str> {
+    let probes = LINES.iter().map(|(name, _)| async move {
+        probe_line(client, name)
+            .await
+            .ok()
+            .map(|cost| (*name, cost))
+    });
+
+    let (line, cost) = join_all(probes)
+        .await
+        .into_iter()
+        .flatten()
+        .min_by_key(|(_, cost)| *cost)
+        .ok_or_else(|| anyhow::anyhow!("所有上传线路均不可用"))?;
+    debug!("线路探测完成: {} ({:?})", line, cost);
+    Ok(line)
+}
+
+/// 上传分片使用的客户端，不携带登录信息
+pub fn chunk_client(proxy: Option<&str>) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(UPLOAD_USER_AGENT)
+        .connect_timeout(Duration::from_secs(15))
+        .timeout(Duration::from_secs(300));
+    if let Some(proxy) = proxy.filter(|proxy| !proxy.is_empty()) {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// 测速时向探测地址发送的数据大小
+const BENCHMARK_PAYLOAD: usize = 1 << 20;
+
+/// 单条线路的测速结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineBenchmark {
+    pub line: String,
+    /// 探测地址的响应时间
+    pub latency_ms: Option<u64>,
+    /// 上传测试数据的速率，单位字节每秒
+    pub throughput: Option<f64>,
+    pub error: Option<String>,
+}
+
+async fn benchmark_line(client: &reqwest::Client, line: &str) -> LineBenchmark {
+    let mut result = LineBenchmark {
+        line: line.to_string(),
+        latency_ms: None,
+        throughput: None,
+        error: None,
+    };
+
+    match probe_line(client, line).await {
+        Ok(cost) => result.latency_ms = Some(cost.as_millis() as u64),
+        Err(e) => {
+            result.error = Some(format!("探测失败: {e}"));
+            return result;
+        }
+    }
+
+    // 与网页端的线路探测一样把测试数据发到探测地址，不创建上传会话
+    let upload = async {
+        let begin = Instant::now();
+        let resp = client
+            .post(probe_url(line))
+            .body(vec![0u8; BENCHMARK_PAYLOAD])
+            .timeout(Duration::from_secs(60))
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            anyhow::bail!("{}", resp.status());
+        }
+        Ok::<_, anyhow::Error>(BENCHMARK_PAYLOAD as f64 / begin.elapsed().as_secs_f64().max(0.001))
+    };
+    match upload.await {
+        Ok(throughput) => result.throughput = Some(throughput),
+        Err(e) => result.error = Some(format!("上传测试失败: {e}")),
+    }
+    result
+}
+
+/// 依次测试每条线路的延迟和上传速率，按速率从高到低排序，失败的线路排在最后
+pub async fn benchmark(proxy: Option<&str>) -> Vec<LineBenchmark> {
+    let client = match chunk_client(proxy) {
+        Ok(client) => client,
+        Err(e) => {
+            return LINES
+                .iter()
+                .map(|(line, _)| LineBenchmark {
+                    line: line.to_string(),
+                    latency_ms: None,
+                    throughput: None,
+                    error: Some(e.to_string()),
+                })
+                .collect();
+        }
+    };
+
+    // 逐条测试，避免线路之间争抢带宽
+    let mut results = Vec::with_capacity(LINES.len());
+    for (line, _) in LINES {
+        let result = benchmark_line(&client, line).await;
+        debug!("线路测速: {:?}", result);
+        results.push(result);
+    }
+
+    results.sort_by(|a, b| {
+        b.throughput
+            .unwrap_or(-1.0)
+            .total_cmp(&a.throughput.unwrap_or(-1.0))
+            .then(
+                a.latency_ms
+                    .unwrap_or(u64::MAX)
+                    .cmp(&b.latency_ms.unwrap_or(u64::MAX)),
+            )
+    });
+    results
+}
+
+/// 服务器拒绝了上传会话，需要重新预上传
+#[derive(Debug)]
+pub struct SessionRejected(pub StatusCode);
+
+impl fmt::Display for SessionRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "上传会话已失效: {}", self.0)
+    }
+}
+
+impl std::error::Error for SessionRejected {}
+
+/// 账号未登录或登录已失效，重新登录前无法继续上传
+#[derive(Debug)]
+pub struct LoginRequired(pub String);
+
+impl fmt::Display for LoginRequired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "登录已失效: {}", self.0)
+    }
+}
+
+impl std::error::Error for LoginRequired {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadedPart {
+    pub index: u32,
+    pub etag: String,
+}
+
+/// 上传完成后服务器返回的视频信息
+#[derive(Debug, Clone)]
+pub struct UploadedVideo {
+    pub filename: String,
+    pub cid: u64,
+}
+
+/// 预上传得到的 upos 会话，记录已上传的分片以便断点续传
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadSession {
+    pub line: String,
+    pub url: String,
+    /// upos 上传凭证，不随任务推送给前端和 HTTP 接口，单独保存在 upload_sessions.json
+    #[serde(skip_serializing, default)]
+    pub auth: String,
+    pub upload_id: String,
+    pub upos_uri: String,
+    pub biz_id: u64,
+    pub chunk_size: u64,
+    pub total_size: u64,
+    pub created_at: usize,
+    #[serde(default)]
+    pub parts: Vec<UploadedPart>,
+}
+
+impl UploadSession {
+    /// 预上传并初始化分片上传
+    pub async fn pre_upload(
+        bilibili: &BiliBili,
+        line: &str,
+        filepath: &Path,
+        total_size: u64,
+    ) -> Result<Self> {
+        let query = line_query(line).ok_or_else(|| anyhow::anyhow!("未知的上传线路: {line}"))?;
+        let file_name = filepath
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow::anyhow!("无效的文件名: {:?}", filepath))?;
+
+        let params = json!({
+            "r": "upos",
+            "profile": "ugcupos/bup",
+            "ssl": 0,
+            "version": UPLOAD_CLIENT_VERSION,
+            "build": UPLOAD_CLIENT_BUILD,
+            "name": file_name,
+            "size": total_size,
+        });
+        let resp = bilibili
+            .client
+            .get(format!("https://member.bilibili.com/preupload?{query}"))
+            .query(&params)
+            .send()
+            .await?;
+        if matches!(
+            resp.status(),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        ) {
+            return Err(LoginRequired(format!("预上传被拒绝: {}", resp.status())).into());
+        }
+        let res: Value = resp.json().await?;
+        if res["OK"].as_i64() != Some(1) {
+            return Err(anyhow::anyhow!("预上传失败: {res}"));
+        }
+
+        let field = |key: &str| {
+            res[key]
+                .as_str()
+                .map(String::from)
+                .ok_or_else(|| anyhow::anyhow!("预上传返回缺少 {key}: {res}"))
+        };
+        let endpoint = field("endpoint")?;
+        let upos_uri = field("upos_uri")?;
+        let auth = field("auth")?;
+        let biz_id = res["biz_id"].as_u64().unwrap_or(0);
+        let chunk_size = res["chunk_size"]
+            .as_u64()
+            .filter(|size| *size > 0)
+            .ok_or_else(|| anyhow::anyhow!("预上传返回缺少 chunk_size: {res}"))?;
+
+        let url = format!("https:{}/{}", endpoint, upos_uri.replace("upos://", ""));
+        let ret: Value = bilibili
+            .client
+            .post(format!("{url}?uploads&output=json"))
+            .header("X-Upos-Auth", &auth)
+            .send()
+            .await?
+            .json()
+            .await?;
+        let upload_id = ret["upload_id"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("初始化分片上传失败: {ret}"))?
+            .to_string();
+
+        info!("创建上传会话: {} ({} 线路)", upos_uri, line);
+        Ok(Self {
+            line: line.to_string(),
+            url,
+            auth,
+            upload_id,
+            upos_uri,
+            biz_id,
+            chunk_size,
+            total_size,
+            created_at: chrono::Utc::now().timestamp_millis() as usize,
+            parts: Vec::new(),
+        })
+    }
+
+    /// 会话未过期、凭证未丢失且文件大小未变化时可继续使用
+    pub fn is_resumable(&self, total_size: u64) -> bool {
+        let now = chrono::Utc::now().timestamp_millis() as usize;
+        !self.auth.is_empty()
+            && self.total_size == total_size
+            && now.saturating_sub(self.created_at) < SESSION_TTL_MS
+    }
+
+    pub fn chunks(&self) -> u32 {
+        self.total_size.div_ceil(self.chunk_size) as u32
+    }
+
+    /// 分片在文件中的起止位置
+    pub fn chunk_range(&self, index: u32) -> (u64, u64) {
+        let start = index as u64 * self.chunk_size;
+        let end = (start + self.chunk_size).min(self.total_size);
+        (start, end)
+    }
+
+    /// 尚未上传的分片序号
+    pub fn missing_chunks(&self) -> Vec<u32> {
+        (0..self.chunks())
+            .filter(|index| !self.parts.iter().any(|part| part.index == *index))
+            .collect()
+    }
+
+    pub fn uploaded_bytes(&self) -> u64 {
+        self.parts
+            .iter()
+            .map(|part| {
+                let (start, end) = self.chunk_range(part.index);
+                end - start
+            })
+            .sum()
+    }
+
+    pub fn record_part(&mut self, part: UploadedPart) {
+        if !self.parts.iter().any(|p| p.index == part.index) {
+            self.parts.push(part);
+        }
+    }
+
+    /// 上传单个分片，返回分片的 ETag
+    pub async fn upload_chunk(
+        &self,
+        client: &reqwest::Client,
+        index: u32,
+        body: impl Into<Body>,
+    ) -> Result<String> {
+        let (start, end) = self.chunk_range(index);
+        let resp = client
+            .put(&self.url)
+            .header("X-Upos-Auth", &self.auth)
+            .header(CONTENT_LENGTH, end - start)
+            .query(&[
+                ("partNumber", (index + 1).to_string()),
+                ("uploadId", self.upload_id.clone()),
+                ("chunk", index.to_string()),
+                ("chunks", self.chunks().to_string()),
+                ("size", (end - start).to_string()),
+                ("start", start.to_string()),
+                ("end", end.to_string()),
+                ("total", self.total_size.to_string()),
+            ])
+            .body(body)
+            .send()
+            .await?;
+
+        let status = resp.status();
+        // 只有这几种表示会话已失效，429、408 等其他错误按普通失败重试
+        if matches!(
+            status,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND
+        ) {
+            return Err(SessionRejected(status).into());
+        }
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("分片 {} 上传失败: {}", index, status));
+        }
+
+        let etag = resp
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim_matches('"').to_string())
+            .unwrap_or_else(|| "etag".to_string());
+        Ok(etag)
+    }
+
+    /// 所有分片上传完毕后合并文件
+    pub async fn complete(
+        &self,
+        client: &reqwest::Client,
+        file_name: &str,
+    ) -> Result<UploadedVideo> {
+        let mut parts = self.parts.clone();
+        parts.sort_by_key(|part| part.index);
+        let parts: Vec<Value> = parts
+            .iter()
+            .map(|part| json!({ "partNumber": part.index + 1, "eTag": part.etag }))
+            .collect();
+
+        let res: Value = client
+            .post(&self.url)
+            .header("X-Upos-Auth", &self.auth)
+            .query(&[
+                ("name", file_name.to_string()),
+                ("uploadId", self.upload_id.clone()),
+                ("biz_id", self.biz_id.to_string()),
+                ("output", "json".to_string()),
+                ("profile", "ugcupos/bup".to_string()),
+            ])
+            .json(&json!({ "parts": parts }))
+            .send()
+            .await?
+            .json()
+            .await?;
+        if res["OK"].as_i64() != Some(1) {
+            return Err(anyhow::anyhow!("合并分片失败: {res}"));
+        }
+
+        let filename = Path::new(&self.upos_uri)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+            .to_string();
+        Ok(UploadedVideo {
+            filename,
+            cid: self.biz_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_session(total_size: u64, chunk_size: u64) -> UploadSession {
+        UploadSession {
+            line: "bda2".to_string(),
+            url: String::new(),
+            auth: "auth".to_string(),
+            upload_id: String::new(),
+            upos_uri: "upos://ugcfx2lf/n1.mp4".to_string(),
+            biz_id: 1,
+            chunk_size,
+            total_size,
+            created_at: chrono::Utc::now().timestamp_millis() as usize,
+            parts: Vec::new(),
+        }
+    }
+
+    fn part(index: u32) -> UploadedPart {
+        UploadedPart {
+            index,
+            etag: format!("etag-{index}"),
+        }
+    }
+
+    #[test]
+    fn splits_file_into_chunks() {
+        let session = new_session(25, 10);
+        assert_eq!(session.chunks(), 3);
+        assert_eq!(session.chunk_range(0), (0, 10));
+        assert_eq!(session.chunk_range(1), (10, 20));
+        // 最后一个分片只到文件末尾
+        assert_eq!(session.chunk_range(2), (20, 25));
+
+        assert_eq!(new_session(30, 10).chunks(), 3);
+        assert_eq!(new_session(0, 10).chunks(), 0);
+    }
+
+    #[test]
+    fn tracks_missing_chunks() {
+        let mut session = new_session(25, 10);
+        assert_eq!(session.missing_chunks(), [0, 1, 2]);
+
+        session.record_part(part(2));
+        session.record_part(part(0));
+        // 重复记录的分片只保留一个
+        session.record_part(part(2));
+        assert_eq!(session.parts.len(), 2);
+        assert_eq!(session.missing_chunks(), [1]);
+        assert_eq!(session.uploaded_bytes(), 15);
+
+        session.record_part(part(1));
+        assert!(session.missing_chunks().is_empty());
+        assert_eq!(session.uploaded_bytes(), 25);
+    }
+
+    #[test]
+    fn resumes_only_matching_sessions() {
+        let mut session = new_session(25, 10);
+        assert!(session.is_resumable(25));
+        assert!(!session.is_resumable(26));
+
+        session.created_at -= SESSION_TTL_MS;
+        assert!(!session.is_resumable(25));
+
+        // 凭证没有保存时无法继续
+        let mut session = new_session(25, 10);
+        session.auth.clear();
+        assert!(!session.is_resumable(25));
+    }
+}
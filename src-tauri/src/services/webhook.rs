@@ -0,0 +1,372 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use tokio::{
+    sync::{Mutex, broadcast::error::RecvError},
+    task,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    models::{ConfigRoot, WebhookConfig},
+    services::upload_events::{UploadEvent, UploadEvents},
+    utils::get_webhook_queue_json_path,
+};
+
+/// 单个通知最多尝试的次数
+const MAX_ATTEMPTS: u32 = 8;
+/// 第一次重试前等待的秒数，之后每次翻倍
+const BASE_DELAY_SECS: u64 = 10;
+const MAX_DELAY_SECS: u64 = 3600;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+static NEXT_DELIVERY_ID: AtomicU64 = AtomicU64::new(1);
+
+fn next_delivery_id() -> u64 {
+    NEXT_DELIVERY_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// 等待发送的通知，发送失败后保留在磁盘上，重启后继续重试
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Delivery {
+    /// 进程内的编号，发送完成后按编号找回队列中的通知
+    #[serde(skip, default = "next_delivery_id")]
+    id: u64,
+    /// 正在发送，仍保留在队列和磁盘上，退出时未完成的通知重启后重新发送
+    #[serde(skip)]
+    in_flight: bool,
+    webhook: String,
+    url: String,
+    event: String,
+    body: String,
+    secret: String,
+    signature_header: String,
+    attempts: u32,
+    next_attempt_at: usize,
+    last_error: Option<String>,
+}
+
+/// 把上传事件转换为 webhook 通知并在后台发送
+#[derive(Clone)]
+pub struct WebhookService {
+    config: Arc<Mutex<ConfigRoot>>,
+    queue: Arc<Mutex<Vec<Delivery>>>,
+    client: reqwest::Client,
+}
+
+impl WebhookService {
+    /// 恢复未送达的通知，订阅上传事件并开始发送
+    pub fn start(config: Arc<Mutex<ConfigRoot>>, events: &UploadEvents) -> Self {
+        let queue = match load_queue() {
+            Ok(queue) => queue,
+            Err(e) => {
+                error!("恢复 webhook 通知失败: {}", e);
+                Vec::new()
+            }
+        };
+        if !queue.is_empty() {
+            info!("已恢复 {} 个未送达的 webhook 通知", queue.len());
+        }
+
+        let service = Self {
+            config,
+            queue: Arc::new(Mutex::new(queue)),
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        };
+
+        let mut rx = events.subscribe();
+        let listener = service.clone();
+        task::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => listener.dispatch(&event).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("webhook 订阅落后，丢弃 {} 个事件", skipped)
+                    }
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+
+        let sender = service.clone();
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                sender.deliver_due().await;
+            }
+        });
+        service
+    }
+
+    /// 按各个 webhook 的事件过滤和模板生成通知
+    async fn dispatch(&self, event: &UploadEvent) {
+        let Some((name, data)) = webhook_event(event) else {
+            return;
+        };
+        let webhooks: Vec<WebhookConfig> = self
+            .config
+            .lock()
+            .await
+            .webhooks
+            .iter()
+            .filter(|webhook| webhook.accepts(name))
+            .cloned()
+            .collect();
+        if webhooks.is_empty() {
+            return;
+        }
+
+        let now = chrono::Utc::now();
+        let mut payload = data;
+        payload["event"] = name.into();
+        payload["time"] = now.to_rfc3339().into();
+        let mut queue = self.queue.lock().await;
+        for webhook in webhooks {
+            debug!("webhook 通知: {} -> {}", name, webhook.name);
+            queue.push(Delivery {
+                id: next_delivery_id(),
+                in_flight: false,
+                webhook: webhook.name,
+                url: webhook.url,
+                event: name.to_string(),
+                body: render(&webhook.payload_template, &payload),
+                secret: webhook.secret,
+                signature_header: webhook.signature_header,
+                attempts: 0,
+                next_attempt_at: now.timestamp_millis() as usize,
+                last_error: None,
+            });
+        }
+        save_queue(&queue).await;
+    }
+
+    /// 发送到期的通知，失败的按指数退避重新排队。
+    /// 发送期间通知留在队列中并标记为发送中，送达或放弃后才移除，期间保存队列不会丢失
+    async fn deliver_due(&self) {
+        let now = chrono::Utc::now().timestamp_millis() as usize;
+        let due: Vec<Delivery> = {
+            let mut queue = self.queue.lock().await;
+            queue
+                .iter_mut()
+                .filter(|delivery| !delivery.in_flight && delivery.next_attempt_at <= now)
+                .map(|delivery| {
+                    delivery.in_flight = true;
+                    delivery.clone()
+                })
+                .collect()
+        };
+        if due.is_empty() {
+            return;
+        }
+
+        for delivery in due {
+            let result = self.send(&delivery).await;
+            let mut queue = self.queue.lock().await;
+            let Some(index) = queue.iter().position(|d| d.id == delivery.id) else {
+                continue;
+            };
+            match result {
+                Ok(()) => {
+                    info!(
+                        "webhook 通知已送达: {} {}",
+                        delivery.webhook, delivery.event
+                    );
+                    queue.remove(index);
+                }
+                Err(e) => {
+                    let queued = &mut queue[index];
+                    queued.in_flight = false;
+                    queued.attempts += 1;
+                    queued.last_error = Some(e.to_string());
+                    if queued.attempts >= MAX_ATTEMPTS {
+                        error!(
+                            "webhook 通知多次发送失败，放弃: {} {} - {}",
+                            delivery.webhook, delivery.event, e
+                        );
+                        queue.remove(index);
+                    } else {
+                        let delay =
+                            (BASE_DELAY_SECS << (queued.attempts - 1).min(16)).min(MAX_DELAY_SECS);
+                        warn!(
+                            "webhook 通知发送失败，{} 秒后重试: {} {} - {}",
+                            delay, delivery.webhook, delivery.event, e
+                        );
+                        queued.next_attempt_at =
+                            chrono::Utc::now().timestamp_millis() as usize + delay as usize * 1000;
+                    }
+                }
+            }
+            save_queue(&queue).await;
+        }
+    }
+
+    async fn send(&self, delivery: &Delivery) -> Result<()> {
+        let mut request = self
+            .client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Biliup-Event", &delivery.event)
+            .body(delivery.body.clone());
+        if !delivery.secret.is_empty() {
+            request = request.header(
+                &delivery.signature_header,
+                sign(&delivery.secret, &delivery.body)?,
+            );
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// 需要通知的事件及其数据，其他事件返回 None
+fn webhook_event(event: &UploadEvent) -> Option<(&'static str, Value)> {
+    let (name, data) = match event {
+        UploadEvent::StatusChanged(payload) => {
+            ("task.status_changed", serde_json::to_value(payload))
+        }
+        UploadEvent::Completed(task) => (
+            "task.completed",
+            Ok(json!({
+                "task_id": task.id,
+                "title": task.video.title,
+                "uid": task.user.uid,
+                "template": task.template,
+                "status": task.status,
+                "filename": task.video.filename,
+                // 上传完成后分P的路径已被清空，使用创建任务时记录的源文件
+                "path": task.source.as_ref().map_or(&task.video.path, |source| &source.path),
+            })),
+        ),
+        UploadEvent::Failed(payload) => ("task.failed", serde_json::to_value(payload)),
+        UploadEvent::SubmitResult(payload) if payload.success => {
+            ("submit.succeeded", serde_json::to_value(payload))
+        }
+        UploadEvent::SubmitResult(payload) => ("submit.failed", serde_json::to_value(payload)),
+        _ => return None,
+    };
+    data.ok().map(|data| (name, data))
+}
+
+/// 用事件数据替换模板中的 {{字段}}，{{json}} 替换为完整的事件 JSON
+fn render(template: &str, payload: &Value) -> String {
+    let json = payload.to_string();
+    if template.trim().is_empty() {
+        return json;
+    }
+
+    let mut body = template.replace("{{json}}", &json);
+    if let Some(fields) = payload.as_object() {
+        for (key, value) in fields {
+            let text = match value {
+                Value::String(text) => text.clone(),
+                Value::Null => String::new(),
+                other => other.to_string(),
+            };
+            // 变量通常放在 JSON 字符串中，按 JSON 转义但不带引号
+            let escaped = serde_json::to_string(&text).unwrap_or_default();
+            let escaped = &escaped[1..escaped.len() - 1];
+            body = body.replace(&format!("{{{{{key}}}}}"), escaped);
+        }
+    }
+    body
+}
+
+fn sign(secret: &str, body: &str) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| anyhow::anyhow!("签名密钥无效: {e}"))?;
+    mac.update(body.as_bytes());
+    Ok(format!(
+        "sha256={}",
+        hex::encode(mac.finalize().into_bytes())
+    ))
+}
+
+fn load_queue() -> Result<Vec<Delivery>> {
+    let path = get_webhook_queue_json_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// 调用方持有队列锁，保证按顺序写入
+async fn save_queue(queue: &[Delivery]) {
+    let content = match serde_json::to_string_pretty(queue) {
+        Ok(content) => content,
+        Err(e) => {
+            error!("保存 webhook 通知失败: {}", e);
+            return;
+        }
+    };
+    match task::spawn_blocking(move || write_queue(content)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("保存 webhook 通知失败: {}", e),
+        Err(e) => error!("保存 webhook 通知失败: {}", e),
+    }
+}
+
+fn write_queue(content: String) -> Result<()> {
+    let path = get_webhook_queue_json_path()?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, content)?;
+    std::fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_body_with_hmac_sha256() {
+        assert_eq!(
+            sign("key", "The quick brown fox jumps over the lazy dog").unwrap(),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        assert_ne!(sign("key", "{}").unwrap(), sign("other", "{}").unwrap());
+    }
+
+    #[test]
+    fn empty_template_sends_event_json() {
+        let payload = json!({ "event": "task.completed", "uid": 1 });
+        assert_eq!(render("  ", &payload), payload.to_string());
+        assert_eq!(
+            render(r#"{"data":{{json}}}"#, &payload),
+            format!(r#"{{"data":{payload}}}"#)
+        );
+    }
+
+    #[test]
+    fn render_escapes_fields_for_json_strings() {
+        let payload = json!({
+            "title": "say \"hi\"\nnow",
+            "uid": 42,
+            "error": null,
+        });
+        let body = render(
+            r#"{"text":"{{title}} ({{uid}}){{error}}","other":"{{missing}}"}"#,
+            &payload,
+        );
+        assert_eq!(
+            body,
+            r#"{"text":"say \"hi\"\nnow (42)","other":"{{missing}}"}"#
+        );
+        let parsed: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed["text"], "say \"hi\"\nnow (42)");
+    }
+}
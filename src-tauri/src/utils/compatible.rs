@@ -132,6 +132,7 @@ impl CompatibilityConverter {
                 cover: streamer_config.cover,
                 title: streamer_config.title,
                 title_prefix: String::new(),
+                part_title: String::new(),
                 desc: streamer_config.desc,
                 desc_v2: streamer_config.desc_v2,
                 dynamic: streamer_config.dynamic,
@@ -152,6 +153,7 @@ impl CompatibilityConverter {
                             status_desc: String::new(), // ɰûstatus_descֶΣ
                             group_key: String::new(), // ɰûзֶ
                             group_role: String::new(), // ɰûзֶ
+                            probe: None,
                         });
                     }
                     vids
@@ -203,6 +205,8 @@ impl CompatibilityConverter {
             limit: legacy.limit,
             watermark: 0,
             auto_edit: 0,
+            bandwidth_limit: 0,
+            max_concurrent: 0,
             templates: template,
         };
 
@@ -0,0 +1,28 @@
+use std::process::{Command, Output};
+
+use tokio::task;
+
+/// 在后台线程调用 ffmpeg 套件中的程序（ffmpeg、ffprobe），从系统 PATH 查找
+pub async fn run(program: &'static str, args: Vec<String>) -> std::io::Result<Output> {
+    task::spawn_blocking(move || Command::new(program).args(&args).output())
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+/// 调用失败时给用户的提示
+pub fn spawn_error(program: &str, e: &std::io::Error) -> String {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        format!("未找到 {program}，请先安装 ffmpeg 并确保其在系统 PATH 中")
+    } else {
+        format!("调用 {program} 失败: {e}")
+    }
+}
+
+/// 取 stderr 中最后一行非空输出作为错误说明
+pub fn last_error_line(output: &Output) -> Option<String> {
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .map(|line| line.trim().to_string())
+}
@@ -153,6 +153,48 @@ pub fn get_config_json_path() -> Result<PathBuf> {
     Ok(config_dir.join("config.json"))
 }
 
+/// 获取上传队列持久化文件路径
+pub fn get_upload_queue_json_path() -> Result<PathBuf> {
+    let config_dir = get_config_dir()?;
+    Ok(config_dir.join("upload_queue.json"))
+}
+
+/// 获取上传会话凭证持久化文件路径
+pub fn get_upload_sessions_json_path() -> Result<PathBuf> {
+    let config_dir = get_config_dir()?;
+    Ok(config_dir.join("upload_sessions.json"))
+}
+
+/// 获取自动投稿草稿持久化文件路径
+pub fn get_drafts_json_path() -> Result<PathBuf> {
+    let config_dir = get_config_dir()?;
+    Ok(config_dir.join("drafts.json"))
+}
+
+/// 获取投稿账本文件路径
+pub fn get_publish_ledger_path() -> Result<PathBuf> {
+    let config_dir = get_config_dir()?;
+    Ok(config_dir.join("publish_ledger.jsonl"))
+}
+
+/// 获取未送达的 webhook 通知持久化文件路径
+pub fn get_webhook_queue_json_path() -> Result<PathBuf> {
+    let config_dir = get_config_dir()?;
+    Ok(config_dir.join("webhook_queue.json"))
+}
+
+/// 获取标题翻译缓存文件路径
+pub fn get_translation_cache_json_path() -> Result<PathBuf> {
+    let config_dir = get_config_dir()?;
+    Ok(config_dir.join("translation_cache.json"))
+}
+
+/// 获取文件夹监控已入队文件的记录路径
+pub fn get_watch_history_json_path() -> Result<PathBuf> {
+    let config_dir = get_config_dir()?;
+    Ok(config_dir.join("watch_history.json"))
+}
+
 /// 获取config.yaml文件路径
 pub fn get_config_yaml_path() -> Result<PathBuf> {
     let config_dir = get_config_dir()?;
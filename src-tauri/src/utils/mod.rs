@@ -1,5 +1,6 @@
 pub mod compatible;
 pub mod crypto;
+pub mod ffmpeg;
 pub mod file_utils;
 
 pub use compatible::*;
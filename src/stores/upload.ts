@@ -1,6 +1,7 @@
 import { defineStore } from 'pinia'
 import { ref } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
 import { useUtilsStore } from './utils'
 
 interface UploadTask {
@@ -12,16 +13,69 @@ interface UploadTask {
     progress: number
     total_transmit_bytes: number
     speed: number
+    speed_bytes_per_sec: number
+    eta_secs?: number
+    average_speed: number
     total_size: number
     error_message?: string
     created_at: number
     started_at?: number
     finished_at?: number
     retry_count: number
+    attempts?: { started_at?: number; failed_at: number; line?: string; kind: string; error: string }[]
+    next_retry_at?: number
+    error_kind?: string
+    pause_reason?: 'User' | 'Schedule'
+    priority?: number // 数值越大越先开始
+}
+
+interface Draft {
+    id: string
+    uid: number
+    template: string
+    task_ids: string[]
+    status: 'Uploading' | 'Submitting' | 'Submitted' | 'Failed'
+    error_message?: string
+    aid?: number
+    bvid?: string
+    created_at: number
+    submitted_at?: number
+    append_to?: number
+}
+
+interface UploadEvent {
+    type: string
+    payload: any
+}
+
+// 模板变量渲染后的内容，标题已加上前缀
+export interface TemplatePreview {
+    title: string
+    desc: string
+    dynamic: string
+    tag: string
+    parts: string[]
+}
+
+export interface LineBenchmark {
+    line: string
+    latency_ms?: number
+    throughput?: number // 字节每秒
+    error?: string
+}
+
+// 单个账号的线路测速结果，已按速度排序
+export interface AccountBenchmark {
+    uid: number
+    proxy?: string
+    tested_at: number
+    lines: LineBenchmark[]
 }
 
 export const useUploadStore = defineStore('upload', () => {
     const uploadQueue = ref<UploadTask[]>([])
+    // 后台自动投稿的稿件，由 draft-updated 事件更新
+    const drafts = ref<Draft[]>([])
     const utilsStore = useUtilsStore()
 
     // 创建上传任务
@@ -93,26 +147,55 @@ export const useUploadStore = defineStore('upload', () => {
         }
     }
 
+    // 调整任务在队列中的位置，队列顺序由 queue-reordered 事件同步
+    const moveTask = async (taskId: string, position: 'top' | 'bottom') => {
+        try {
+            const command =
+                position === 'top' ? 'move_upload_task_to_top' : 'move_upload_task_to_bottom'
+            return await invoke<boolean>(command, { taskId })
+        } catch (error) {
+            console.error('移动任务失败:', error)
+            throw error
+        }
+    }
+
+    // 设置任务优先级
+    const setTaskPriority = async (taskId: string, priority: number) => {
+        try {
+            return await invoke<boolean>('set_upload_priority', { taskId, priority })
+        } catch (error) {
+            console.error('设置任务优先级失败:', error)
+            throw error
+        }
+    }
+
+    // 测试各上传线路，自动选择线路时按结果排序
+    const benchmarkLines = async (uid?: number) => {
+        try {
+            return await invoke<AccountBenchmark[]>('benchmark_lines', { uid })
+        } catch (error) {
+            console.error('线路测速失败:', error)
+            throw error
+        }
+    }
+
     // 获取上传队列
     const getUploadQueue = async () => {
         try {
             const queue: UploadTask[] = await invoke('get_upload_queue')
             uploadQueue.value = queue
-            // 更新速率
+            // 速率由后端按滑动窗口计算
             const now = Date.now()
-            queue
-                .filter(task => task.started_at && task.status === 'Running')
-                .forEach(task => {
-                    const elapsed = now - task.started_at!
-                    task.speed = elapsed > 0 ? (task.total_transmit_bytes * 1000) / elapsed : 0
-                })
+            queue.forEach(task => {
+                task.speed = task.speed_bytes_per_sec || 0
+            })
 
             queue
                 .filter(
                     task =>
                         task.started_at &&
                         task.status === 'Running' &&
-                        task.speed === 0 &&
+                        !task.average_speed &&
                         now - task.started_at! > 30000
                 )
                 .forEach(task => {
@@ -151,9 +234,9 @@ export const useUploadStore = defineStore('upload', () => {
     }
 
     // 提交视频
-    const submitTemplate = async (uid: number, upload: any) => {
+    const submitTemplate = async (uid: number, upload: any, template?: string) => {
         try {
-            const result = await invoke('submit', { uid, form: upload })
+            const result = await invoke('submit', { uid, form: upload, template })
             return result
         } catch (error) {
             console.error('提交视频失败:', error)
@@ -161,6 +244,164 @@ export const useUploadStore = defineStore('upload', () => {
         }
     }
 
+    // 检查投稿信息，返回字段级的错误、警告和自动修正后的模板
+    const validateTemplate = async (template: any) => {
+        try {
+            return await invoke('validate_template', { template })
+        } catch (error) {
+            console.error('检查投稿信息失败:', error)
+            throw error
+        }
+    }
+
+    // 由后端按翻译配置翻译标题
+    const translateTitle = async (text: string): Promise<string> => {
+        return await invoke('translate_title', { text })
+    }
+
+    // 用示例文件预览模板变量渲染后的标题、简介和分P标题
+    const previewTemplate = async (uid: number, template: any, path?: string) => {
+        try {
+            return await invoke<TemplatePreview>('preview_template', { uid, template, path })
+        } catch (error) {
+            console.error('预览模板失败:', error)
+            throw error
+        }
+    }
+
+    // 登记自动投稿，分P全部上传完成后由后端投稿
+    const createDraft = async (uid: number, template: string, taskIds: string[]) => {
+        try {
+            return await invoke('create_draft', { uid, template, taskIds })
+        } catch (error) {
+            console.error('登记自动投稿失败:', error)
+            throw error
+        }
+    }
+
+    const getDrafts = async () => {
+        try {
+            drafts.value = await invoke('get_drafts')
+        } catch (error) {
+            console.error('获取自动投稿列表失败:', error)
+            throw error
+        }
+    }
+
+    const appendParts = async (uid: number, aid: number, taskIds: string[]) => {
+        try {
+            return await invoke('append_parts', { uid, aid, taskIds })
+        } catch (error) {
+            console.error('登记追加分P失败:', error)
+            throw error
+        }
+    }
+
+    // 监听后端推送的上传事件，get_upload_queue 仅用于重新同步
+    let unlistenFns: UnlistenFn[] = []
+    const listenUploadEvents = async () => {
+        if (unlistenFns.length > 0) {
+            return
+        }
+
+        const applyEvent = (event: UploadEvent) => {
+            const payload = event.payload
+            switch (event.type) {
+                case 'task-created':
+                case 'task-updated':
+                case 'completed': {
+                    const index = uploadQueue.value.findIndex(t => t.id === payload.id)
+                    if (index >= 0) {
+                        uploadQueue.value[index] = payload
+                    } else {
+                        uploadQueue.value.push(payload)
+                    }
+                    break
+                }
+                case 'task-removed':
+                    uploadQueue.value = uploadQueue.value.filter(t => t.id !== payload)
+                    break
+                case 'queue-reordered': {
+                    const order: string[] = payload
+                    uploadQueue.value = [...uploadQueue.value].sort(
+                        (a, b) => order.indexOf(a.id) - order.indexOf(b.id)
+                    )
+                    break
+                }
+                case 'progress': {
+                    const task = uploadQueue.value.find(t => t.id === payload.task_id)
+                    if (task) {
+                        task.progress = payload.progress
+                        task.total_transmit_bytes = payload.total_transmit_bytes
+                        task.speed_bytes_per_sec = payload.speed_bytes_per_sec
+                        task.speed = payload.speed_bytes_per_sec
+                        task.eta_secs = payload.eta_secs ?? undefined
+                        task.average_speed = payload.average_speed
+                    }
+                    break
+                }
+                case 'status-changed':
+                case 'failed': {
+                    // 事件中的标题、账号和模板只用于通知，不写回任务
+                    const task = uploadQueue.value.find(t => t.id === payload.task_id)
+                    if (task) {
+                        task.status = payload.status
+                        task.error_message = payload.error_message ?? undefined
+                        task.pause_reason = payload.pause_reason ?? undefined
+                    }
+                    break
+                }
+                case 'draft-updated': {
+                    const index = drafts.value.findIndex(d => d.id === payload.id)
+                    if (index >= 0) {
+                        drafts.value[index] = payload
+                    } else {
+                        drafts.value.push(payload)
+                    }
+                    break
+                }
+                case 'submit-result': {
+                    const target = `模板"${payload.template}"`
+                    if (payload.success) {
+                        const bvid = payload.bvid ? `: ${payload.bvid}` : ''
+                        utilsStore.showMessage(`${target}投稿成功${bvid}`, 'success')
+                    } else {
+                        utilsStore.showMessage(
+                            `${target}投稿失败: ${payload.error_message}`,
+                            'error'
+                        )
+                    }
+                    break
+                }
+                case 'watch-rejected':
+                    utilsStore.showMessage(
+                        `监控文件未加入上传队列: ${payload.path} - ${payload.reason}`,
+                        'warning'
+                    )
+                    break
+            }
+        }
+
+        const names = [
+            'task-created',
+            'progress',
+            'status-changed',
+            'completed',
+            'failed',
+            'task-removed',
+            'task-updated',
+            'queue-reordered',
+            'draft-updated',
+            'submit-result',
+            'watch-rejected'
+        ]
+        unlistenFns = await Promise.all(
+            names.map(name =>
+                listen<UploadEvent>(`upload://${name}`, event => applyEvent(event.payload))
+            )
+        )
+    }
+
     const getUploadTask = (taskId: string) => {
         const task = uploadQueue.value.find(t => t.id === taskId)
         if (task) {
@@ -172,13 +413,24 @@ export const useUploadStore = defineStore('upload', () => {
 
     return {
         uploadQueue,
+        drafts,
         createUploadTask,
         startUpload,
         pauseUpload,
         cancelUpload,
         getUploadQueue,
         retryUpload,
+        moveTask,
+        setTaskPriority,
+        benchmarkLines,
         submitTemplate,
-        getUploadTask
+        validateTemplate,
+        previewTemplate,
+        translateTitle,
+        createDraft,
+        getDrafts,
+        appendParts,
+        getUploadTask,
+        listenUploadEvents
     }
 })
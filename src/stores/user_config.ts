@@ -17,6 +17,7 @@ export interface TemplateConfig {
     cover: string // 封面URL
     title: string
     title_prefix: string
+    part_title?: string // 分P标题模板
     desc: string
     desc_v2?: string
     dynamic: string // 粉丝动?
@@ -50,9 +51,22 @@ interface UserConfig {
     limit: number
     watermark: number
     auto_edit: number
+    max_concurrent?: number
     templates: Record<string, TemplateConfig> // 模板?-> 模板配置
 }
 
+// 文件夹监控，由后端监控文件夹并加入上传队列
+export interface WatchConfig {
+    path: string
+    enabled: boolean
+    filter: string
+    recursive: boolean
+    uid: number
+    template: string
+    stable_secs: number
+    auto_start: boolean
+}
+
 // 配置根接?
 interface ConfigRoot {
     max_curr: number
@@ -64,6 +78,9 @@ interface ConfigRoot {
     translation_model: string
     translation_prompt: string
     translation_auto: boolean
+    translation_auto_parts?: boolean // 创建任务时翻译分P标题
+    translation_provider?: 'openai' | 'deepl' | 'ollama'
+    watches?: WatchConfig[]
     config: Record<number, UserConfig> // uid -> 用户配置
 }
 
@@ -138,6 +155,7 @@ export const useUserConfigStore = defineStore('userConfig', () => {
         cover: '',
         title: '',
         title_prefix: '',
+        part_title: '',
         desc: '',
         desc_v2: undefined,
         dynamic: '',
@@ -266,6 +284,8 @@ export const useUserConfigStore = defineStore('userConfig', () => {
                 translation_model: '',
                 translation_prompt: '',
                 translation_auto: false,
+                translation_auto_parts: false,
+                translation_provider: 'openai',
                 config: {}
             }
         }
@@ -401,7 +421,7 @@ export const useUserConfigStore = defineStore('userConfig', () => {
     // 更新用户基础配置
     const updateUserConfig = async (
         userUid: number,
-        updates: Partial<Pick<UserConfig, 'line' | 'proxy' | 'limit' | 'watermark' | 'auto_edit'>>
+        updates: Partial<Pick<UserConfig, 'line' | 'proxy' | 'limit' | 'watermark' | 'auto_edit' | 'max_concurrent'>>
     ) => {
         if (!configRoot.value) {
             throw new Error('配置未加载')
@@ -431,6 +451,10 @@ export const useUserConfigStore = defineStore('userConfig', () => {
             userConfig.auto_edit = updates.auto_edit!
         }
 
+        if ('max_concurrent' in updates) {
+            userConfig.max_concurrent = updates.max_concurrent!
+        }
+
         try {
             await invoke('save_user_config', {
                 uid: userUid,
@@ -438,7 +462,8 @@ export const useUserConfigStore = defineStore('userConfig', () => {
                 proxy: userConfig.proxy,
                 limit: userConfig.limit,
                 watermark: userConfig.watermark,
-                autoEdit: userConfig.auto_edit
+                autoEdit: userConfig.auto_edit,
+                maxConcurrent: userConfig.max_concurrent
             })
             // 保存配置
             await saveConfig()
@@ -450,7 +475,7 @@ export const useUserConfigStore = defineStore('userConfig', () => {
     }
 
     const updateGlobalConfig = async (
-        updates: Partial<Pick<ConfigRoot, 'max_curr' | 'auto_upload' | 'auto_start' | 'log_level' | 'translation_api_url' | 'translation_api_key' | 'translation_model' | 'translation_prompt' | 'translation_auto'>>
+        updates: Partial<Pick<ConfigRoot, 'max_curr' | 'auto_upload' | 'auto_start' | 'log_level' | 'translation_api_url' | 'translation_api_key' | 'translation_model' | 'translation_prompt' | 'translation_auto' | 'translation_auto_parts' | 'translation_provider'>>
     ) => {
         if (!configRoot.value) {
             throw new Error('配置未加载')
@@ -469,7 +494,9 @@ export const useUserConfigStore = defineStore('userConfig', () => {
                 translationApiKey: configRoot.value.translation_api_key,
                 translationModel: configRoot.value.translation_model,
                 translationPrompt: configRoot.value.translation_prompt,
-                translationAuto: configRoot.value.translation_auto
+                translationAuto: configRoot.value.translation_auto,
+                translationProvider: configRoot.value.translation_provider,
+                translationAutoParts: configRoot.value.translation_auto_parts ?? false
             })
             // 保存配置
             await saveConfig()
@@ -480,6 +507,18 @@ export const useUserConfigStore = defineStore('userConfig', () => {
         return true
     }
 
+    // 保存文件夹监控设置，后端按新设置重启监控
+    const updateWatches = async (watches: WatchConfig[]) => {
+        if (!configRoot.value) {
+            throw new Error('配置未加载')
+        }
+
+        await invoke('save_watches', { watches })
+        configRoot.value.watches = watches
+        await saveConfig()
+        return true
+    }
+
     return {
         // 状?
         configRoot,
@@ -505,6 +544,7 @@ export const useUserConfigStore = defineStore('userConfig', () => {
         duplicateUserTemplate,
         updateUserConfig,
         updateGlobalConfig,
+        updateWatches,
         createDefaultTemplate
     }
 })